@@ -9,7 +9,17 @@ use strong_xml::{XmlRead, XmlWrite};
 #[derive(Debug, Clone, XmlRead, XmlWrite, Serialize, Deserialize)]
 #[xml(tag = "launch")]
 pub struct Launch {
-    #[xml(child = "")]
+    #[xml(
+        child = "arg",
+        child = "let",
+        child = "executable",
+        child = "node",
+        child = "group",
+        child = "include",
+        child = "set-env",
+        child = "unset-env",
+        child = "push-ros-namespace"
+    )]
     pub children: Vec<LaunchChild>,
 }
 
@@ -32,6 +42,8 @@ pub enum LaunchChild {
     SetEnv(SetEnv),
     #[xml(tag = "unset-env")]
     UnsetEnv(UnsetEnv),
+    #[xml(tag = "push-ros-namespace")]
+    PushRosNamespace(PushRosNamespace),
 }
 
 #[derive(Debug, Clone, XmlRead, XmlWrite, Serialize, Deserialize)]
@@ -68,7 +80,7 @@ pub struct Include {
     #[xml(attr = "unless")]
     pub unless: Option<String>,
 
-    #[xml(child = "")]
+    #[xml(child = "arg")]
     pub arg: Vec<IncludeArg>,
 }
 
@@ -114,7 +126,7 @@ pub struct Node {
     #[xml(attr = "unless")]
     pub unless: Option<String>,
 
-    #[xml(child = "")]
+    #[xml(child = "env", child = "param", child = "remap")]
     pub children: Vec<NodeChild>,
 }
 
@@ -148,7 +160,7 @@ pub struct Param {
     #[xml(attr = "sep")]
     pub sep: Option<String>,
     #[xml(attr = "value")]
-    pub value: String,
+    pub value: Option<String>,
 }
 
 #[derive(Debug, Clone, XmlRead, XmlWrite, Serialize, Deserialize)]
@@ -191,13 +203,24 @@ pub struct Group {
     #[xml(attr = "scoped")]
     pub scoped: Option<bool>,
 
+    #[xml(attr = "ns")]
+    pub ns: Option<String>,
+
     #[xml(attr = "if")]
     pub r#if: Option<String>,
 
     #[xml(attr = "unless")]
     pub unless: Option<String>,
 
-    #[xml(child = "")]
+    #[xml(
+        child = "executable",
+        child = "node",
+        child = "group",
+        child = "include",
+        child = "set-env",
+        child = "unset-env",
+        child = "push-ros-namespace"
+    )]
     pub children: Vec<GroupChild>,
 }
 
@@ -216,6 +239,8 @@ pub enum GroupChild {
     SetEnv(SetEnv),
     #[xml(tag = "unset-env")]
     UnsetEnv(UnsetEnv),
+    #[xml(tag = "push-ros-namespace")]
+    PushRosNamespace(PushRosNamespace),
 }
 
 #[derive(Debug, Clone, XmlRead, XmlWrite, Serialize, Deserialize)]
@@ -247,6 +272,13 @@ pub struct UnsetEnv {
     pub unless: Option<String>,
 }
 
+#[derive(Debug, Clone, XmlRead, XmlWrite, Serialize, Deserialize)]
+#[xml(tag = "push-ros-namespace")]
+pub struct PushRosNamespace {
+    #[xml(attr = "namespace")]
+    pub namespace: String,
+}
+
 #[derive(Debug, Clone, XmlRead, XmlWrite, Serialize, Deserialize)]
 #[xml(tag = "env")]
 pub struct Env {
@@ -1,10 +1,29 @@
 use anyhow::{bail, Result};
 use clap::Parser;
 use itertools::Itertools;
-use std::path::PathBuf;
+use launch_parse::{LaunchOptions, WorkspaceLocator};
+use launch_subst::{CommandPolicy, CommandRunner};
+use std::{path::PathBuf, time::Duration};
 
 #[derive(Parser)]
 struct Opts {
+    /// Locate packages in a source workspace instead of the ament
+    /// index.
+    #[clap(long)]
+    pub workspace: Option<PathBuf>,
+    /// Seed the generator of $(anon) names for reproducible output.
+    #[clap(long)]
+    pub anon_seed: Option<u64>,
+    /// Allow $(command) to run any command.
+    #[clap(long)]
+    pub allow_commands: bool,
+    /// Allow $(command) to run this program, given by an absolute path
+    /// or a name searched in PATH. It can be given multiple times.
+    #[clap(long, conflicts_with = "allow_commands")]
+    pub allow_command: Vec<String>,
+    /// Kill commands run by $(command) after this many seconds.
+    #[clap(long)]
+    pub command_timeout: Option<f64>,
     pub input_file: PathBuf,
     pub args: Vec<String>,
 }
@@ -23,7 +42,28 @@ fn main() -> Result<()> {
         })
         .try_collect()?;
 
-    let profile = launch_parse::load_launch_file(opts.input_file, args)?;
+    let policy = if opts.allow_commands {
+        CommandPolicy::Allow
+    } else if !opts.allow_command.is_empty() {
+        CommandPolicy::Allowlist(opts.allow_command)
+    } else {
+        CommandPolicy::Deny
+    };
+
+    let mut options = LaunchOptions {
+        anon_seed: opts.anon_seed,
+        command_runner: CommandRunner {
+            policy,
+            timeout: opts.command_timeout.map(Duration::from_secs_f64),
+            cwd: None,
+        },
+        ..LaunchOptions::default()
+    };
+    if let Some(dir) = opts.workspace {
+        options.locator = Box::new(WorkspaceLocator::scan(dir)?);
+    }
+
+    let profile = launch_parse::load_launch_file(opts.input_file, args, &options)?;
     dbg!(profile);
 
     Ok(())
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use clap::Parser;
+use launch_parse::{LaunchOptions, WorkspaceLocator};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+struct Opts {
+    /// Locate packages in a source workspace instead of the ament
+    /// index.
+    #[clap(long)]
+    pub workspace: Option<PathBuf>,
+    pub input_file: PathBuf,
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::parse();
+
+    let mut options = LaunchOptions::default();
+    if let Some(dir) = opts.workspace {
+        options.locator = Box::new(WorkspaceLocator::scan(dir)?);
+    }
+
+    let args = launch_parse::declared_args(opts.input_file, &options)?;
+
+    for arg in args {
+        println!("'{}':", arg.name);
+        println!(
+            "    {}",
+            arg.description.as_deref().unwrap_or("no description given")
+        );
+        match (&arg.default, &arg.default_preview) {
+            (Some(default), Some(preview)) if preview != default => {
+                println!("    (default: '{default}', evaluated to '{preview}')")
+            }
+            (Some(default), _) => println!("    (default: '{default}')"),
+            (None, _) => println!("    (required)"),
+        }
+    }
+
+    Ok(())
+}
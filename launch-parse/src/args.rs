@@ -0,0 +1,236 @@
+use crate::{
+    absolute_path, group_child_tag, launch_child_tag, read_launch_file, ElementCounter, FileFrame,
+    LaunchOptions, Result, State,
+};
+use launch_format::{Group, GroupChild, Include, IncludeArg, Launch, LaunchArg, LaunchChild, Let};
+use launch_subst::to_source;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// A launch argument declared by an `<arg>` element.
+#[derive(Debug, Clone)]
+pub struct DeclaredArg {
+    pub name: String,
+    pub description: Option<String>,
+
+    /// The default value as written in the launch file. Substitutions
+    /// in it are not evaluated.
+    pub default: Option<String>,
+
+    /// The default value with the substitutions that can be evaluated
+    /// replaced by their values, e.g. `/opt/ros/share/nav/$(arg map)`.
+    pub default_preview: Option<String>,
+
+    /// Whether the argument must be provided by the user.
+    pub required: bool,
+
+    /// The launch file declaring the argument.
+    pub file: PathBuf,
+}
+
+/// Lists the arguments declared by the launch file and the files it
+/// includes unconditionally, like `ros2 launch --show-args`.
+///
+/// Launch files are not fully evaluated. Missing required arguments
+/// are not errors, and includes whose paths cannot be evaluated
+/// without them are skipped. Groups and includes with `if` or `unless`
+/// attributes are not visited. `$(command)` is never run, even if the
+/// options allow it.
+pub fn declared_args<P>(path: P, options: &LaunchOptions) -> Result<Vec<DeclaredArg>>
+where
+    P: AsRef<Path>,
+{
+    let mut state = State::new(options);
+    let mut visited = HashSet::new();
+    let mut args = vec![];
+
+    walk_file(path.as_ref(), vec![], &mut state, &mut visited, &mut args)?;
+
+    Ok(args)
+}
+
+fn walk_file(
+    path: &Path,
+    include_args: Vec<(String, String)>,
+    state: &mut State,
+    visited: &mut HashSet<PathBuf>,
+    args: &mut Vec<DeclaredArg>,
+) -> Result<()> {
+    let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if !visited.insert(canonical_path.clone()) {
+        return Ok(());
+    }
+
+    let frame = FileFrame {
+        path: path.to_path_buf(),
+        absolute_path: absolute_path(path),
+        canonical_path,
+        args: include_args.clone(),
+        elements: vec![],
+    };
+
+    state.with_file(frame, |state| {
+        let launch: Launch = read_launch_file(path, state)?;
+
+        state.with_scope(|state| {
+            for (name, value) in include_args {
+                state.insert_var(name, value);
+            }
+
+            state.with_element("launch".to_string(), |state| {
+                let mut counter = ElementCounter::default();
+
+                for child in &launch.children {
+                    let element = counter.next(launch_child_tag(child));
+
+                    state.with_element(element, |state| match child {
+                        LaunchChild::Arg(arg) => {
+                            declare_arg(arg, state, args);
+                            Ok(())
+                        }
+                        LaunchChild::Let(Let { name, value }) => {
+                            if let Some(value) = state.eval_known(value) {
+                                state.insert_var(name.to_string(), value);
+                            }
+                            Ok(())
+                        }
+                        LaunchChild::Group(group) => walk_group(group, state, visited, args),
+                        LaunchChild::Include(include) => {
+                            walk_include(include, state, visited, args)
+                        }
+                        _ => Ok(()),
+                    })?;
+                }
+
+                Ok(())
+            })
+        })
+    })
+}
+
+fn walk_group(
+    group: &Group,
+    state: &mut State,
+    visited: &mut HashSet<PathBuf>,
+    args: &mut Vec<DeclaredArg>,
+) -> Result<()> {
+    if group.r#if.is_some() || group.unless.is_some() {
+        return Ok(());
+    }
+
+    let mut counter = ElementCounter::default();
+
+    for child in &group.children {
+        let element = counter.next(group_child_tag(child));
+
+        state.with_element(element, |state| match child {
+            GroupChild::Group(group) => walk_group(group, state, visited, args),
+            GroupChild::Include(include) => walk_include(include, state, visited, args),
+            _ => Ok(()),
+        })?;
+    }
+
+    Ok(())
+}
+
+fn walk_include(
+    include: &Include,
+    state: &mut State,
+    visited: &mut HashSet<PathBuf>,
+    args: &mut Vec<DeclaredArg>,
+) -> Result<()> {
+    let Include {
+        file,
+        r#if,
+        unless,
+        arg,
+    } = include;
+
+    if r#if.is_some() || unless.is_some() {
+        return Ok(());
+    }
+
+    let Some(path) = state.eval_known(file) else {
+        return Ok(());
+    };
+    let include_args: Vec<_> = arg
+        .iter()
+        .filter_map(|IncludeArg { name, value }| {
+            let value = state.eval_known(value)?;
+            Some((name.to_string(), value))
+        })
+        .collect();
+
+    walk_file(Path::new(&path), include_args, state, visited, args)
+}
+
+/// Records the argument and defines it if the value can be evaluated,
+/// so that later includes may refer to it.
+fn declare_arg(arg: &LaunchArg, state: &mut State, args: &mut Vec<DeclaredArg>) {
+    let LaunchArg {
+        name,
+        value,
+        default,
+        description,
+    } = arg;
+
+    let default = value.as_ref().or(default.as_ref());
+
+    args.push(DeclaredArg {
+        name: name.to_string(),
+        description: description.clone(),
+        default: default.cloned(),
+        default_preview: default.map(|default| to_source(&state.eval_partial(default))),
+        required: default.is_none(),
+        file: state.current_file().to_path_buf(),
+    });
+
+    // A fixed value always applies, while a default value applies only
+    // if the argument is not given.
+    if value.is_none() && state.contains_var(name) {
+        return;
+    }
+    if let Some(value) = default.and_then(|default| state.eval_known(default)) {
+        state.insert_var(name.to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use launch_subst::CommandPolicy;
+    use std::fs;
+
+    #[test]
+    fn declared_args_never_run_commands() {
+        let dir = std::env::temp_dir().join(format!("launch-parse-args-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("ran");
+        let launch = dir.join("test.launch.xml");
+        fs::write(
+            &launch,
+            format!(
+                r#"<launch>
+  <let name="x" value="$(command 'touch {marker}')"/>
+  <arg name="robot" default="r1"/>
+  <arg name="model" default="$(var robot)_$(var x)"/>
+</launch>"#,
+                marker = marker.display()
+            ),
+        )
+        .unwrap();
+
+        let mut options = LaunchOptions::default();
+        options.command_runner.policy = CommandPolicy::Allow;
+        let args = declared_args(&launch, &options);
+        let ran = marker.exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let args = args.unwrap();
+        assert!(!ran);
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[1].default_preview.as_deref(), Some("r1_$(var x)"));
+    }
+}
@@ -1,5 +1,10 @@
-use launch_format::{Output, Param, Remap};
-use std::{collections::HashMap, path::PathBuf};
+use crate::env::EnvDiff;
+use launch_format::{Output, Remap};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    path::PathBuf,
+};
 
 #[derive(Debug, Clone)]
 pub struct Launch {
@@ -16,7 +21,10 @@ pub struct Executable {
     pub shell: Option<String>,
     pub launch_prefix: Option<String>,
     pub output: Option<Output>,
+    /// The effective environment of the process.
     pub env: HashMap<String, String>,
+    /// The changes of `env` against the base environment.
+    pub env_diff: EnvDiff,
 }
 
 #[derive(Debug, Clone)]
@@ -26,10 +34,126 @@ pub struct Node {
     pub name: Option<String>,
     pub ros_args: Option<String>,
     pub args: Option<String>,
-    pub namespace: Option<String>,
+    /// The fully qualified namespace of the node.
+    pub namespace: String,
     pub launch_prefix: Option<String>,
     pub output: Option<Output>,
+    /// The effective environment of the process.
     pub env: HashMap<String, String>,
-    pub param: Vec<Param>,
+    /// The changes of `env` against the base environment.
+    pub env_diff: EnvDiff,
+    /// The parameters in the order they are applied. Later ones
+    /// override earlier ones with the same name.
+    pub param: Vec<Parameter>,
     pub remap: Vec<Remap>,
 }
+
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    /// The parameter name. Nested names are joined by dots.
+    pub name: String,
+    pub value: ParamValue,
+    /// The launch file or the parameter file defining the parameter.
+    pub source: PathBuf,
+}
+
+/// A typed parameter value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamValue {
+    Bool(bool),
+    Integer(i64),
+    Double(f64),
+    String(String),
+    BoolArray(Vec<bool>),
+    IntegerArray(Vec<i64>),
+    DoubleArray(Vec<f64>),
+    StringArray(Vec<String>),
+}
+
+impl ParamValue {
+    /// Converts the value to a YAML value, e.g. to write a parameter
+    /// file.
+    pub fn to_yaml(&self) -> serde_yaml::Value {
+        use serde_yaml::Value;
+
+        match self {
+            ParamValue::Bool(value) => Value::from(*value),
+            ParamValue::Integer(value) => Value::from(*value),
+            ParamValue::Double(value) => Value::from(*value),
+            ParamValue::String(value) => Value::from(value.as_str()),
+            ParamValue::BoolArray(values) => values.iter().copied().map(Value::from).collect(),
+            ParamValue::IntegerArray(values) => values.iter().copied().map(Value::from).collect(),
+            ParamValue::DoubleArray(values) => values.iter().copied().map(Value::from).collect(),
+            ParamValue::StringArray(values) => values
+                .iter()
+                .map(|value| Value::from(value.as_str()))
+                .collect(),
+        }
+    }
+}
+
+/// Formats the value in the YAML flow style accepted by
+/// `--ros-args -p NAME:=VALUE`. Strings are quoted if they would be
+/// read as another type.
+impl Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_list<T>(
+            f: &mut fmt::Formatter<'_>,
+            values: &[T],
+            write_item: impl Fn(&mut fmt::Formatter<'_>, &T) -> fmt::Result,
+        ) -> fmt::Result {
+            write!(f, "[")?;
+            for (idx, value) in values.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                write_item(f, value)?;
+            }
+            write!(f, "]")
+        }
+
+        match self {
+            ParamValue::Bool(value) => write!(f, "{value}"),
+            ParamValue::Integer(value) => write!(f, "{value}"),
+            ParamValue::Double(value) => write_double(f, *value),
+            ParamValue::String(value) => write_string(f, value, false),
+            ParamValue::BoolArray(values) => write_list(f, values, |f, value| write!(f, "{value}")),
+            ParamValue::IntegerArray(values) => {
+                write_list(f, values, |f, value| write!(f, "{value}"))
+            }
+            ParamValue::DoubleArray(values) => {
+                write_list(f, values, |f, value| write_double(f, *value))
+            }
+            ParamValue::StringArray(values) => {
+                write_list(f, values, |f, value| write_string(f, value, true))
+            }
+        }
+    }
+}
+
+fn write_double(f: &mut fmt::Formatter<'_>, value: f64) -> fmt::Result {
+    if value.is_nan() {
+        write!(f, ".nan")
+    } else if value.is_infinite() {
+        let sign = if value < 0.0 { "-" } else { "" };
+        write!(f, "{sign}.inf")
+    } else {
+        // The Debug format always keeps a decimal point or an
+        // exponent, so that the value is read back as a double.
+        write!(f, "{value:?}")
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str, in_list: bool) -> fmt::Result {
+    let plain = matches!(
+        serde_yaml::from_str::<serde_yaml::Value>(value),
+        Ok(serde_yaml::Value::String(ref parsed)) if parsed == value
+    );
+    let plain = plain && !(in_list && value.contains([',', '[', ']', '{', '}']));
+
+    if plain {
+        write!(f, "{value}")
+    } else {
+        write!(f, "'{}'", value.replace('\'', "''"))
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Layered environment variables.
+///
+/// The bottom layer is the base environment, which is usually the
+/// environment of the launching process. Each scope pushes a layer on
+/// top that records variables set or unset by `<set-env>` and
+/// `<unset-env>`. A process adds one more layer for its `<env>`
+/// elements.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    base: HashMap<String, String>,
+    layers: Vec<HashMap<String, Option<String>>>,
+}
+
+impl Environment {
+    pub fn new(base: HashMap<String, String>) -> Self {
+        Self {
+            base,
+            layers: vec![],
+        }
+    }
+
+    /// Creates an environment based on a snapshot of the process
+    /// environment. Variables that are not valid Unicode are skipped.
+    pub fn from_process() -> Self {
+        let base = std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        Self::new(base)
+    }
+
+    pub fn base(&self) -> &HashMap<String, String> {
+        &self.base
+    }
+
+    pub fn push_layer(&mut self) {
+        self.layers.push(HashMap::new());
+    }
+
+    pub fn pop_layer(&mut self) {
+        self.layers.pop();
+    }
+
+    /// Returns the value of the variable seen from the top layer.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        match self.layers.iter().rev().find_map(|layer| layer.get(name)) {
+            Some(value) => value.as_deref(),
+            None => self.base.get(name).map(|value| value.as_str()),
+        }
+    }
+
+    /// Sets the variable in the top layer.
+    pub fn set(&mut self, name: String, value: String) {
+        self.top_layer().insert(name, Some(value));
+    }
+
+    /// Unsets the variable in the top layer and returns the previous
+    /// value.
+    pub fn unset(&mut self, name: &str) -> Option<String> {
+        let prev = self.get(name)?.to_string();
+        self.top_layer().insert(name.to_string(), None);
+        Some(prev)
+    }
+
+    /// Returns all variables seen from the top layer.
+    pub fn effective(&self) -> HashMap<String, String> {
+        let mut vars = self.base.clone();
+
+        for layer in &self.layers {
+            for (name, value) in layer {
+                match value {
+                    Some(value) => {
+                        vars.insert(name.clone(), value.clone());
+                    }
+                    None => {
+                        vars.remove(name);
+                    }
+                }
+            }
+        }
+
+        vars
+    }
+
+    /// Returns the changes of the effective environment against the
+    /// base environment.
+    pub fn diff(&self) -> EnvDiff {
+        let mut diff = EnvDiff::default();
+        let names: BTreeSet<&String> = self.layers.iter().flat_map(|layer| layer.keys()).collect();
+
+        for name in names {
+            match (self.base.get(name.as_str()), self.get(name)) {
+                (Some(prev), Some(value)) if prev == value => {}
+                (_, Some(value)) => {
+                    diff.set.insert(name.clone(), value.to_string());
+                }
+                (Some(_), None) => {
+                    diff.unset.insert(name.clone());
+                }
+                (None, None) => {}
+            }
+        }
+
+        diff
+    }
+
+    fn top_layer(&mut self) -> &mut HashMap<String, Option<String>> {
+        if self.layers.is_empty() {
+            self.push_layer();
+        }
+        self.layers.last_mut().unwrap()
+    }
+}
+
+/// The changes of an environment against its base environment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvDiff {
+    /// Variables that are added or changed.
+    pub set: BTreeMap<String, String>,
+
+    /// Variables of the base environment that are removed.
+    pub unset: BTreeSet<String>,
+}
@@ -0,0 +1,245 @@
+use launch_subst::{EvalError, Rule, SubstError};
+use std::{
+    fmt::{self, Display},
+    io,
+    path::PathBuf,
+};
+
+/// The error type returned when a launch file cannot be loaded.
+#[derive(Debug, thiserror::Error)]
+pub enum LaunchError {
+    #[error("{location}: unable to read the launch file: {source}")]
+    Io {
+        source: io::Error,
+        location: Box<Location>,
+    },
+
+    #[error("{location}: the launch file must end with '.xml' or '.yaml'")]
+    UnsupportedFileType { location: Box<Location> },
+
+    #[error("{location}: unable to parse the XML launch file: {source}")]
+    Xml {
+        source: strong_xml::XmlError,
+        location: Box<Location>,
+    },
+
+    #[error("{location}: unable to parse the YAML launch file: {source}")]
+    Yaml {
+        source: serde_yaml::Error,
+        location: Box<Location>,
+    },
+
+    #[error("{location}: invalid substitution syntax\n{source}")]
+    Syntax {
+        source: Box<pest::error::Error<Rule>>,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: unable to evaluate the expression "{expr}": {source}"#)]
+    Eval {
+        expr: String,
+        source: EvalError,
+        location: Box<Location>,
+    },
+
+    #[error("{location}: include cycle detected: {}", DisplayChain(chain))]
+    IncludeCycle {
+        chain: Vec<IncludeHop>,
+        location: Box<Location>,
+    },
+
+    #[error("{location}: the include depth exceeds the limit {max_depth}")]
+    IncludeDepthExceeded {
+        max_depth: usize,
+        location: Box<Location>,
+    },
+
+    #[error("{location}: the attribute is required")]
+    MissingAttribute { location: Box<Location> },
+
+    #[error("{location}: unable to load the parameter file {}: {reason}", path.display())]
+    InvalidParamFile {
+        path: PathBuf,
+        reason: String,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: invalid value of the parameter "{name}": {reason}"#)]
+    InvalidParamValue {
+        name: String,
+        reason: String,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: the argument "{name}" is required but not provided"#)]
+    MissingArgument {
+        name: String,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: the argument "{name}" is not defined"#)]
+    UndefinedArgument {
+        name: String,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: the environment variable "{name}" is not set"#)]
+    UndefinedEnv {
+        name: String,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: unable to unset the environment variable "{name}" that is not set"#)]
+    UnsetUndefinedEnv {
+        name: String,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: expect a condition "true", "false", "1" or "0", but get "{value}""#)]
+    InvalidBool {
+        value: String,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: unable to find the package "{pkg}""#)]
+    PackageNotFound {
+        pkg: String,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: unable to find the executable "{name}""#)]
+    ExecutableNotFound {
+        name: String,
+        location: Box<Location>,
+    },
+
+    #[error("{location}: {source}")]
+    Substitution {
+        source: SubstError,
+        location: Box<Location>,
+    },
+
+    #[error(r#"{location}: the substitution "{name}" is not supported"#)]
+    UnsupportedSubstitution {
+        name: String,
+        location: Box<Location>,
+    },
+}
+
+impl LaunchError {
+    /// Returns where the error occurs.
+    pub fn location(&self) -> &Location {
+        match self {
+            LaunchError::Io { location, .. }
+            | LaunchError::UnsupportedFileType { location }
+            | LaunchError::Xml { location, .. }
+            | LaunchError::Yaml { location, .. }
+            | LaunchError::Syntax { location, .. }
+            | LaunchError::Eval { location, .. }
+            | LaunchError::IncludeCycle { location, .. }
+            | LaunchError::IncludeDepthExceeded { location, .. }
+            | LaunchError::MissingAttribute { location }
+            | LaunchError::InvalidParamFile { location, .. }
+            | LaunchError::InvalidParamValue { location, .. }
+            | LaunchError::MissingArgument { location, .. }
+            | LaunchError::UndefinedArgument { location, .. }
+            | LaunchError::UndefinedEnv { location, .. }
+            | LaunchError::UnsetUndefinedEnv { location, .. }
+            | LaunchError::InvalidBool { location, .. }
+            | LaunchError::PackageNotFound { location, .. }
+            | LaunchError::ExecutableNotFound { location, .. }
+            | LaunchError::Substitution { location, .. }
+            | LaunchError::UnsupportedSubstitution { location, .. } => location,
+        }
+    }
+}
+
+/// Points to an element or an attribute in a launch file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// The launch file containing the element.
+    pub file: PathBuf,
+
+    /// The path to the element in the file, e.g.
+    /// `launch/group[2]/node[0]`. It is empty if the error is not
+    /// caused by a specific element.
+    pub element: String,
+
+    /// The attribute of the element, if any.
+    pub attribute: Option<String>,
+
+    /// The chain of launch files from the top-level file to `file`,
+    /// inclusively.
+    pub include_stack: Vec<PathBuf>,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+
+        if !self.element.is_empty() {
+            write!(f, ": {}", self.element)?;
+        }
+
+        if let Some(attr) = &self.attribute {
+            write!(f, "@{attr}")?;
+        }
+
+        if let [outer @ .., _] = self.include_stack.as_slice() {
+            if !outer.is_empty() {
+                write!(f, " (included from ")?;
+
+                for (idx, path) in outer.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, " -> ")?;
+                    }
+                    write!(f, "{}", path.display())?;
+                }
+
+                write!(f, ")")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A launch file in an include chain and the arguments passed to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeHop {
+    pub file: PathBuf,
+    pub args: Vec<(String, String)>,
+}
+
+impl Display for IncludeHop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+
+        if !self.args.is_empty() {
+            write!(f, " [")?;
+            for (idx, (name, value)) in self.args.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{name}:={value}")?;
+            }
+            write!(f, "]")?;
+        }
+
+        Ok(())
+    }
+}
+
+struct DisplayChain<'a>(&'a [IncludeHop]);
+
+impl Display for DisplayChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, hop) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{hop}")?;
+        }
+        Ok(())
+    }
+}
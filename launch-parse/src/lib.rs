@@ -1,32 +1,43 @@
+mod args;
 pub mod context;
+mod env;
+mod error;
+mod locator;
+mod namespace;
+mod options;
+mod params;
+
+pub use args::{declared_args, DeclaredArg};
+pub use env::{EnvDiff, Environment};
+pub use error::{IncludeHop, LaunchError, Location};
+pub use locator::{AmentIndexLocator, MemoryLocator, PackageLocator, WorkspaceLocator};
+pub use options::LaunchOptions;
 
-use anyhow::{bail, ensure, Context, Result};
 use launch_format::{
-    Executable, Group, GroupChild, Include, IncludeArg, Launch, LaunchArg, LaunchChild, Let, Node,
-    NodeChild, SetEnv, UnsetEnv,
+    Env, Executable, Group, GroupChild, Include, IncludeArg, Launch, LaunchArg, LaunchChild, Let,
+    Node, NodeChild, Param, PushRosNamespace, Remap, SetEnv, UnsetEnv,
+};
+use launch_subst::{
+    AnonNames, CommandOutput, SubstBlock, SubstError, SubstitutionContext, SubstitutionRegistry,
+    Value,
 };
-use launch_subst::{SubstBlock, Substitution};
 use std::{
-    borrow::Cow,
+    cell::RefCell,
     collections::HashMap,
     fs::File,
     io::BufReader,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 use strong_xml::XmlRead;
 
-pub fn load_launch_file<P, I>(path: P, args: I) -> Result<context::Launch>
+type Result<T, E = LaunchError> = std::result::Result<T, E>;
+
+pub fn load_launch_file<P, I>(path: P, args: I, options: &LaunchOptions) -> Result<context::Launch>
 where
     I: IntoIterator<Item = (String, String)>,
     P: AsRef<Path>,
 {
-    let mut state = State {
-        work_dirs: vec![],
-        scopes: vec![],
-        execs: vec![],
-        nodes: vec![],
-    };
-
+    let mut state = State::new(options);
     load_launch_file_private(path, args, &mut state)?;
 
     let State { execs, nodes, .. } = state;
@@ -35,87 +46,172 @@ where
     Ok(profile)
 }
 
+/// Evaluates the substitutions in the text that are known from the
+/// launch arguments and the options, and keeps the others as
+/// substitutions, e.g. `/opt/ros/share/nav/$(var map)` for
+/// `$(find-pkg-share nav)/$(var map)` if `map` is not given.
+///
+/// It never fails. Text that cannot be parsed is kept as literal text,
+/// and `$(command)` is never run.
+pub fn eval_partial<'t, I>(text: &'t str, args: I, options: &LaunchOptions) -> Vec<SubstBlock<'t>>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut state = State::new(options);
+
+    state.with_scope(|state| {
+        for (name, value) in args {
+            state.insert_var(name, value);
+        }
+        state.eval_partial(text)
+    })
+}
+
 fn load_launch_file_private<P, I>(path: P, args: I, state: &mut State) -> Result<()>
 where
     I: IntoIterator<Item = (String, String)>,
     P: AsRef<Path>,
 {
     let path = path.as_ref();
+    let args: Vec<_> = args.into_iter().collect();
 
-    let Some(ext) = path.extension() else {
-        bail!(
-            "The launch file must ends with '.xml' or '.yaml': {}",
-            path.display()
-        );
-    };
-    let Some(parent) = path.parent() else {
-        bail!(
-            "Unable to find the parent directory of the launch file: {}",
-            path.display()
-        );
-    };
+    // The canonical path identifies the file in cycle detection. If
+    // the file does not exist, the error is reported on reading.
+    let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
 
-    let launch: Launch = if ext == "xml" {
-        let text = std::fs::read_to_string(path)
-            .with_context(|| format!("Unable to open {}", path.display()))?;
-        Launch::from_str(&text).with_context(|| format!("Unable to parse {}", path.display()))?
-    } else if ext == "yaml" {
-        let reader = BufReader::new(
-            File::open(path).with_context(|| format!("Unable to open {}", path.display()))?,
-        );
-        serde_yaml::from_reader(reader)
-            .with_context(|| format!("Unable to parse {}", path.display()))?
-    } else {
-        bail!(
-            "The launch file must ends with '.xml' or '.yaml': {}",
-            path.display()
-        );
+    if !state.files.is_empty() {
+        if let Some(max_depth) = state.options.max_include_depth {
+            if state.files.len() > max_depth {
+                return Err(LaunchError::IncludeDepthExceeded {
+                    max_depth,
+                    location: state.location(Some("file")),
+                });
+            }
+        }
+
+        if let Some(start) = state
+            .files
+            .iter()
+            .position(|frame| frame.canonical_path == canonical_path)
+        {
+            let mut chain: Vec<_> = state.files[start..]
+                .iter()
+                .map(|frame| IncludeHop {
+                    file: frame.path.clone(),
+                    args: frame.args.clone(),
+                })
+                .collect();
+            chain.push(IncludeHop {
+                file: path.to_path_buf(),
+                args,
+            });
+
+            return Err(LaunchError::IncludeCycle {
+                chain,
+                location: state.location(Some("file")),
+            });
+        }
+    }
+
+    let frame = FileFrame {
+        path: path.to_path_buf(),
+        absolute_path: absolute_path(path),
+        canonical_path,
+        args: args.clone(),
+        elements: vec![],
     };
 
-    state.with_wd(parent.to_path_buf(), |state| {
+    state.with_file(frame, |state| {
+        let launch = read_launch_file(path, state)?;
+
         state.with_scope(|state| {
             for (name, value) in args {
                 state.insert_var(name, value);
             }
-            parse_launch(&launch, state)
+            state.with_element("launch".to_string(), |state| parse_launch(&launch, state))
         })
-    })?;
+    })
+}
 
-    Ok(())
+/// Reads and parses the launch file of the current file frame.
+fn read_launch_file(path: &Path, state: &State) -> Result<Launch> {
+    let ext = path.extension();
+
+    let launch: Launch = if ext.is_some_and(|ext| ext == "xml") {
+        let text = std::fs::read_to_string(path).map_err(|source| LaunchError::Io {
+            source,
+            location: state.location(None),
+        })?;
+        Launch::from_str(&text).map_err(|source| LaunchError::Xml {
+            source,
+            location: state.location(None),
+        })?
+    } else if ext.is_some_and(|ext| ext == "yaml") {
+        let file = File::open(path).map_err(|source| LaunchError::Io {
+            source,
+            location: state.location(None),
+        })?;
+        serde_yaml::from_reader(BufReader::new(file)).map_err(|source| LaunchError::Yaml {
+            source,
+            location: state.location(None),
+        })?
+    } else {
+        return Err(LaunchError::UnsupportedFileType {
+            location: state.location(None),
+        });
+    };
+
+    Ok(launch)
 }
 
 fn parse_launch(launch: &Launch, state: &mut State) -> Result<()> {
+    let mut counter = ElementCounter::default();
+
     for child in &launch.children {
-        match child {
-            LaunchChild::Arg(LaunchArg {
-                name,
-                value,
-                default,
-                ..
-            }) => match (value, default) {
-                (None, None) => {
-                    ensure!(
-                        state.contains_var(name),
-                        r#"The argument "{name}" is required but not provided."#
-                    );
-                }
-                (None, Some(default)) => {
-                    state.get_var_or_insert(name, default);
-                }
-                (Some(value), _) => {
-                    state.insert_var(name.to_string(), value.to_string());
+        let element = counter.next(launch_child_tag(child));
+
+        state.with_element(element, |state| {
+            match child {
+                LaunchChild::Arg(LaunchArg {
+                    name,
+                    value,
+                    default,
+                    ..
+                }) => match (value, default) {
+                    (None, None) => {
+                        if !state.contains_var(name) {
+                            return Err(LaunchError::MissingArgument {
+                                name: name.to_string(),
+                                location: state.location(Some("name")),
+                            });
+                        }
+                    }
+                    (None, Some(default)) => {
+                        if !state.contains_var(name) {
+                            let default = state.eval("default", default)?;
+                            state.insert_var(name.to_string(), default);
+                        }
+                    }
+                    (Some(value), _) => {
+                        let value = state.eval("value", value)?;
+                        state.insert_var(name.to_string(), value);
+                    }
+                },
+                LaunchChild::Let(Let { name, value }) => {
+                    let value = state.eval("value", value)?;
+                    state.insert_var(name.to_string(), value);
                 }
-            },
-            LaunchChild::Let(Let { name, value }) => {
-                state.insert_var(name.to_string(), value.to_string());
+                LaunchChild::Executable(exec) => parse_executable(exec, state)?,
+                LaunchChild::Node(node) => parse_node(node, state)?,
+                LaunchChild::Group(group) => parse_group(group, state)?,
+                LaunchChild::Include(include) => parse_include(include, state)?,
+                LaunchChild::SetEnv(set_env) => parse_set_env(set_env, state)?,
+                LaunchChild::UnsetEnv(unset_env) => parse_unset_env(unset_env, state)?,
+                LaunchChild::PushRosNamespace(push) => parse_push_ros_namespace(push, state)?,
             }
-            LaunchChild::Executable(exec) => parse_executable(exec, state)?,
-            LaunchChild::Node(node) => parse_node(node, state)?,
-            LaunchChild::Group(group) => parse_group(group, state)?,
-            LaunchChild::Include(include) => parse_include(include, state)?,
-            LaunchChild::SetEnv(set_env) => parse_set_env(set_env, state)?,
-            LaunchChild::UnsetEnv(unset_env) => parse_unset_env(unset_env, state)?,
-        }
+
+            Ok(())
+        })?;
     }
 
     Ok(())
@@ -124,6 +220,7 @@ fn parse_launch(launch: &Launch, state: &mut State) -> Result<()> {
 fn parse_group(group: &Group, state: &mut State) -> Result<()> {
     let Group {
         scoped,
+        ns,
         r#if,
         unless,
         children,
@@ -137,24 +234,39 @@ fn parse_group(group: &Group, state: &mut State) -> Result<()> {
     let scoped = *scoped == Some(true);
 
     let parse_child = |children: &[_], state: &mut State| -> Result<_> {
+        let mut counter = ElementCounter::default();
+
         for child in children {
-            match child {
-                GroupChild::Executable(exec) => parse_executable(exec, state)?,
-                GroupChild::Node(node) => parse_node(node, state)?,
-                GroupChild::Group(group) => parse_group(group, state)?,
-                GroupChild::Include(include) => parse_include(include, state)?,
-                GroupChild::SetEnv(set_env) => parse_set_env(set_env, state)?,
-                GroupChild::UnsetEnv(unset_env) => parse_unset_env(unset_env, state)?,
-            }
+            let element = counter.next(group_child_tag(child));
+
+            state.with_element(element, |state| match child {
+                GroupChild::Executable(exec) => parse_executable(exec, state),
+                GroupChild::Node(node) => parse_node(node, state),
+                GroupChild::Group(group) => parse_group(group, state),
+                GroupChild::Include(include) => parse_include(include, state),
+                GroupChild::SetEnv(set_env) => parse_set_env(set_env, state),
+                GroupChild::UnsetEnv(unset_env) => parse_unset_env(unset_env, state),
+                GroupChild::PushRosNamespace(push) => parse_push_ros_namespace(push, state),
+            })?;
         }
 
         Ok(())
     };
 
+    // The namespace given by the ns attribute only applies to the
+    // children even if the group is not scoped.
+    let ns = state.eval_opt("ns", ns.as_deref())?;
+    let parse_body = |state: &mut State| -> Result<_> {
+        match &ns {
+            Some(ns) => state.with_namespace(ns, |state| parse_child(children, state)),
+            None => parse_child(children, state),
+        }
+    };
+
     if scoped {
-        state.with_scope(|state| parse_child(children, state))?;
+        state.with_scope(parse_body)?;
     } else {
-        parse_child(children, state)?;
+        parse_body(state)?;
     }
 
     Ok(())
@@ -180,14 +292,121 @@ fn parse_node(node: &Node, state: &mut State) -> Result<()> {
         return Ok(());
     }
 
+    let pkg = state.eval("pkg", pkg)?;
+    let exec = state.eval("exec", exec)?;
+    let name = state.eval_opt("name", name.as_deref())?;
+    let ros_args = state.eval_opt("ros-arg", ros_args.as_deref())?;
+    let args = state.eval_opt("arg", args.as_deref())?;
+    let namespace = namespace::node_namespace(
+        state.current_namespace(),
+        state
+            .eval_opt("namespace", namespace.as_deref())?
+            .as_deref(),
+    );
+    let launch_prefix = state.eval_opt("launch-prefix", launch_prefix.as_deref())?;
+
+    let mut env = state.env.clone();
+    env.push_layer();
+    let mut param = vec![];
+    let mut remap = vec![];
+    let mut counter = ElementCounter::default();
+
     for child in children {
-        match child {
-            NodeChild::Env(_) => todo!(),
-            NodeChild::Param(_) => todo!(),
-            NodeChild::Remap(_) => todo!(),
-        }
+        let element = counter.next(node_child_tag(child));
+
+        state.with_element(element, |state| {
+            match child {
+                NodeChild::Env(Env { name, value }) => {
+                    env.set(state.eval("name", name)?, state.eval("value", value)?);
+                }
+                NodeChild::Param(Param {
+                    name: param_name,
+                    from,
+                    sep,
+                    value,
+                }) => {
+                    if let Some(from) = from {
+                        let path = PathBuf::from(state.eval("from", from)?);
+                        let file_params =
+                            params::load_param_file(&path, &namespace, name.as_deref()).map_err(
+                                |reason| LaunchError::InvalidParamFile {
+                                    path: path.clone(),
+                                    reason,
+                                    location: state.location(Some("from")),
+                                },
+                            )?;
+
+                        for (name, value) in file_params {
+                            let value = params::from_yaml(&value).map_err(|reason| {
+                                LaunchError::InvalidParamFile {
+                                    path: path.clone(),
+                                    reason: format!(r#"invalid value of "{name}": {reason}"#),
+                                    location: state.location(Some("from")),
+                                }
+                            })?;
+
+                            param.push(context::Parameter {
+                                name,
+                                value,
+                                source: path.clone(),
+                            });
+                        }
+                    } else {
+                        let Some(param_name) = param_name else {
+                            return Err(state.missing_attribute("name"));
+                        };
+                        let Some(value) = value else {
+                            return Err(state.missing_attribute("value"));
+                        };
+
+                        let param_name = state.eval("name", param_name)?;
+                        let text = state.eval("value", value)?;
+                        let value = match state.eval_opt("sep", sep.as_deref())? {
+                            Some(sep) => params::infer_list(&text, &sep),
+                            None => params::infer_value(&text),
+                        };
+                        let value = value.map_err(|reason| LaunchError::InvalidParamValue {
+                            name: param_name.clone(),
+                            reason,
+                            location: state.location(Some("value")),
+                        })?;
+
+                        param.push(context::Parameter {
+                            name: param_name,
+                            value,
+                            source: state.current_file().to_path_buf(),
+                        });
+                    }
+                }
+                NodeChild::Remap(Remap { from, to }) => {
+                    remap.push(Remap {
+                        from: state.eval("from", from)?,
+                        to: state.eval("to", to)?,
+                    });
+                }
+            }
+
+            Ok(())
+        })?;
     }
-    todo!();
+
+    let node = context::Node {
+        pkg,
+        exec,
+        name,
+        ros_args,
+        args,
+        namespace,
+        launch_prefix,
+        output: *output,
+        env: env.effective(),
+        env_diff: env.diff(),
+        param,
+        remap,
+    };
+    state.nodes.push(node);
+
+    Ok(())
 }
 
 fn parse_executable(exec: &Executable, state: &mut State) -> Result<()> {
@@ -209,7 +428,36 @@ fn parse_executable(exec: &Executable, state: &mut State) -> Result<()> {
         return Ok(());
     }
 
-    todo!();
+    let mut process_env = state.env.clone();
+    process_env.push_layer();
+    for (idx, Env { name, value }) in env.iter().enumerate() {
+        state.with_element(format!("env[{idx}]"), |state| {
+            process_env.set(state.eval("name", name)?, state.eval("value", value)?);
+            Ok(())
+        })?;
+    }
+
+    // A relative working directory is relative to the directory of
+    // the launch file.
+    let cwd = cwd
+        .as_deref()
+        .map(|cwd| -> Result<_> { Ok(state.cwd().join(state.eval("cwd", cwd)?)) })
+        .transpose()?;
+
+    let exec = context::Executable {
+        cmd: state.eval("cmd", cmd)?.into(),
+        cwd,
+        name: state.eval_opt("name", name.as_deref())?,
+        args: state.eval_opt("args", args.as_deref())?,
+        shell: state.eval_opt("shell", shell.as_deref())?,
+        launch_prefix: state.eval_opt("launch-prefix", launch_prefix.as_deref())?,
+        output: *output,
+        env: process_env.effective(),
+        env_diff: process_env.diff(),
+    };
+    state.execs.push(exec);
+
+    Ok(())
 }
 
 fn parse_include(include: &Include, state: &mut State) -> Result<()> {
@@ -225,10 +473,16 @@ fn parse_include(include: &Include, state: &mut State) -> Result<()> {
         return Ok(());
     }
 
-    let path = state.eval(file)?;
-    let args = arg
+    let path = state.eval("file", file)?;
+    let args: Vec<_> = arg
         .iter()
-        .map(|IncludeArg { name, value }| (name.to_string(), value.to_string()));
+        .enumerate()
+        .map(|(idx, IncludeArg { name, value })| {
+            state.with_element(format!("arg[{idx}]"), |state| {
+                Ok((name.to_string(), state.eval("value", value)?))
+            })
+        })
+        .collect::<Result<_>>()?;
     load_launch_file_private(path, args, state)?;
 
     Ok(())
@@ -244,7 +498,9 @@ fn parse_set_env(set_env: &SetEnv, state: &mut State) -> Result<()> {
 
     let yes = state.eval_if_unless(r#if.as_deref(), unless.as_deref())?;
     if yes {
-        state.insert_env(name.to_string(), value.to_string());
+        let name = state.eval("name", name)?;
+        let value = state.eval("value", value)?;
+        state.insert_env(name, value);
     }
 
     Ok(())
@@ -256,98 +512,300 @@ fn parse_unset_env(unset_env: &UnsetEnv, state: &mut State) -> Result<()> {
     if yes {
         let value = state.remove_env(name);
         if value.is_none() {
-            todo!();
+            return Err(LaunchError::UnsetUndefinedEnv {
+                name: name.to_string(),
+                location: state.location(Some("name")),
+            });
         }
     }
     Ok(())
 }
 
-struct State {
-    work_dirs: Vec<PathBuf>,
+fn parse_push_ros_namespace(push: &PushRosNamespace, state: &mut State) -> Result<()> {
+    let PushRosNamespace { namespace } = push;
+    let namespace = state.eval("namespace", namespace)?;
+    state.push_namespace(&namespace);
+    Ok(())
+}
+
+/// Returns the absolute path with `.` and `..` components resolved
+/// lexically. Symbolic links are kept as they are, like Python's
+/// `os.path.abspath()` that ROS uses.
+fn absolute_path(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+fn launch_child_tag(child: &LaunchChild) -> &'static str {
+    match child {
+        LaunchChild::Arg(_) => "arg",
+        LaunchChild::Let(_) => "let",
+        LaunchChild::Executable(_) => "executable",
+        LaunchChild::Node(_) => "node",
+        LaunchChild::Group(_) => "group",
+        LaunchChild::Include(_) => "include",
+        LaunchChild::SetEnv(_) => "set-env",
+        LaunchChild::UnsetEnv(_) => "unset-env",
+        LaunchChild::PushRosNamespace(_) => "push-ros-namespace",
+    }
+}
+
+fn group_child_tag(child: &GroupChild) -> &'static str {
+    match child {
+        GroupChild::Executable(_) => "executable",
+        GroupChild::Node(_) => "node",
+        GroupChild::Group(_) => "group",
+        GroupChild::Include(_) => "include",
+        GroupChild::SetEnv(_) => "set-env",
+        GroupChild::UnsetEnv(_) => "unset-env",
+        GroupChild::PushRosNamespace(_) => "push-ros-namespace",
+    }
+}
+
+fn node_child_tag(child: &NodeChild) -> &'static str {
+    match child {
+        NodeChild::Env(_) => "env",
+        NodeChild::Param(_) => "param",
+        NodeChild::Remap(_) => "remap",
+    }
+}
+
+/// Numbers sibling elements by their tags, e.g. `node[0]`, `group[0]`,
+/// `node[1]`.
+#[derive(Default)]
+struct ElementCounter {
+    counts: HashMap<&'static str, usize>,
+}
+
+impl ElementCounter {
+    pub fn next(&mut self, tag: &'static str) -> String {
+        let count = self.counts.entry(tag).or_insert(0);
+        let element = format!("{tag}[{count}]");
+        *count += 1;
+        element
+    }
+}
+
+struct State<'a> {
+    options: &'a LaunchOptions,
+    env: Environment,
+    /// Names generated for `$(anon)` in this load
+    anon: RefCell<AnonNames>,
+    files: Vec<FileFrame>,
     scopes: Vec<Scope>,
     execs: Vec<context::Executable>,
     nodes: Vec<context::Node>,
 }
 
-impl State {
+impl<'a> State<'a> {
+    pub fn new(options: &'a LaunchOptions) -> Self {
+        let env = match &options.environment {
+            Some(vars) => Environment::new(vars.clone()),
+            None => Environment::from_process(),
+        };
+
+        let anon = match options.anon_seed {
+            Some(seed) => AnonNames::with_seed(seed),
+            None => AnonNames::new(),
+        };
+
+        Self {
+            options,
+            env,
+            anon: RefCell::new(anon),
+            files: vec![],
+            scopes: vec![],
+            execs: vec![],
+            nodes: vec![],
+        }
+    }
+
     pub fn eval_if_unless(&self, r#if: Option<&str>, unless: Option<&str>) -> Result<bool> {
         let if_value = match r#if {
-            Some(cond) => self.eval_bool(cond)?,
+            Some(cond) => self.eval_bool("if", cond)?,
             None => true,
         };
 
         let unless_value = match unless {
-            Some(cond) => self.eval_bool(cond)?,
+            Some(cond) => self.eval_bool("unless", cond)?,
             None => false,
         };
 
         Ok(if_value && !unless_value)
     }
 
-    pub fn eval_bool(&self, text: &str) -> Result<bool> {
-        let text = self.eval(text)?;
+    /// Evaluates a condition. `true` and `1` are true, and `false` and
+    /// `0` are false, ignoring letter case.
+    pub fn eval_bool(&self, attr: &str, text: &str) -> Result<bool> {
+        let text = match self.eval_value(attr, text)? {
+            Value::Str(text) => text,
+            value => value.to_string(),
+        };
+        self.parse_bool(attr, text)
+    }
+
+    /// Evaluates the attribute value. If the value consists of a single
+    /// `$(eval)` substitution, the typed result of the expression is
+    /// returned. Otherwise, the value is a string.
+    pub fn eval_value(&self, attr: &str, text: &str) -> Result<Value> {
+        let blocks = self.parse_subst(attr, text)?;
 
-        let ret = match text.as_str() {
-            "true" => true,
-            "false" => false,
-            _ => bail!("expect 'true' or 'false', but get '{text}'"),
+        let value = match blocks.as_slice() {
+            [SubstBlock::Substitution { subst, .. }] => subst
+                .eval_value(self)
+                .map_err(|err| self.subst_error(attr, err))?,
+            _ => Value::Str(self.eval_blocks(attr, &blocks)?),
         };
-        Ok(ret)
+        Ok(value)
+    }
+
+    /// Evaluates the substitutions in the attribute value.
+    pub fn eval(&self, attr: &str, text: &str) -> Result<String> {
+        let blocks = self.parse_subst(attr, text)?;
+        self.eval_blocks(attr, &blocks)
     }
 
-    pub fn eval(&self, text: &str) -> Result<String> {
-        let blocks = launch_subst::parse(text)?;
-        let mut buf = String::new();
+    fn parse_subst<'t>(&self, attr: &str, text: &'t str) -> Result<Vec<SubstBlock<'t>>> {
+        launch_subst::parse(text).map_err(|source| LaunchError::Syntax {
+            source: Box::new(source),
+            location: self.location(Some(attr)),
+        })
+    }
 
-        for block in &blocks {
-            let text: Cow<_> = match block {
-                SubstBlock::Text(text) => text.into(),
-                SubstBlock::Substitution(subst) => self.subst(subst)?,
-            };
-            buf.push_str(&text);
+    /// Evaluates the known substitutions in the attribute value and
+    /// keeps the others as substitutions. It never fails. Text that
+    /// cannot be parsed is kept as literal text. See [`eval_partial`].
+    pub fn eval_partial<'t>(&self, text: &'t str) -> Vec<SubstBlock<'t>> {
+        match launch_subst::parse(text) {
+            Ok(blocks) => launch_subst::partial_eval(&blocks, self),
+            Err(_) => vec![SubstBlock::text(text, 0..text.len())],
         }
+    }
+
+    /// Evaluates the attribute value if it reduces to text by partial
+    /// evaluation, so that `$(command)` is never run. It returns `None`
+    /// otherwise or if the value cannot be parsed.
+    pub fn eval_known(&self, text: &str) -> Option<String> {
+        let blocks = launch_subst::parse(text).ok()?;
+        match launch_subst::partial_eval(&blocks, self).as_slice() {
+            [] => Some(String::new()),
+            [SubstBlock::Text { text, .. }] => Some(text.to_string()),
+            _ => None,
+        }
+    }
 
-        Ok(buf)
+    pub fn eval_opt(&self, attr: &str, text: Option<&str>) -> Result<Option<String>> {
+        text.map(|text| self.eval(attr, text)).transpose()
     }
 
-    pub fn subst<'a>(&'a self, subst: &'a Substitution) -> Result<Cow<'a, str>> {
-        let text: Cow<'a, str> = match subst {
-            Substitution::Env { variable } => {
-                let Some(value) = self.get_env(variable) else {
-                    todo!();
-                };
-                value.into()
+    /// Evaluates parsed substitution blocks and concatenates the
+    /// results.
+    pub fn eval_blocks(&self, attr: &str, blocks: &[SubstBlock]) -> Result<String> {
+        launch_subst::eval_blocks(blocks, self).map_err(|err| self.subst_error(attr, err))
+    }
+
+    /// Converts a substitution error to a launch error at the
+    /// attribute.
+    fn subst_error(&self, attr: &str, err: SubstError) -> LaunchError {
+        let location = self.location(Some(attr));
+
+        match err {
+            SubstError::UndefinedEnv { name } => LaunchError::UndefinedEnv { name, location },
+            SubstError::UndefinedLaunchConfiguration { name } => {
+                LaunchError::UndefinedArgument { name, location }
             }
-            Substitution::OptEnv {
-                variable,
-                default_value,
-            } => {
-                let Some(value) = self.get_env(variable).or(default_value.as_deref()) else {
-                    todo!();
-                };
-                value.into()
+            SubstError::PackageNotFound { pkg } => LaunchError::PackageNotFound { pkg, location },
+            SubstError::ExecutableNotFound { name } => {
+                LaunchError::ExecutableNotFound { name, location }
             }
-            Substitution::Find { pkg } => todo!(),
-            Substitution::Anon { name } => todo!(),
-            Substitution::Arg { name } => todo!(),
-            Substitution::Eval { expr } => todo!(),
-            Substitution::DirName => todo!(),
-            Substitution::Other { args } => todo!(),
-        };
-        Ok(text)
+            SubstError::InvalidCondition { value } => LaunchError::InvalidBool { value, location },
+            SubstError::Eval { expr, source } => LaunchError::Eval {
+                expr,
+                source,
+                location,
+            },
+            SubstError::Unsupported { name } => {
+                LaunchError::UnsupportedSubstitution { name, location }
+            }
+            source @ (SubstError::UnknownFile
+            | SubstError::Command { .. }
+            | SubstError::CommandDenied { .. }
+            | SubstError::InvalidArgumentCount { .. }
+            | SubstError::Custom { .. }) => LaunchError::Substitution { source, location },
+        }
+    }
+
+    fn parse_bool(&self, attr: &str, text: String) -> Result<bool> {
+        match launch_subst::parse_bool(&text) {
+            Some(value) => Ok(value),
+            None => Err(LaunchError::InvalidBool {
+                value: text,
+                location: self.location(Some(attr)),
+            }),
+        }
+    }
+
+    fn missing_attribute(&self, attr: &str) -> LaunchError {
+        LaunchError::MissingAttribute {
+            location: self.location(Some(attr)),
+        }
+    }
+
+    /// Returns the location of the current element and the attribute.
+    pub fn location(&self, attr: Option<&str>) -> Box<Location> {
+        let frame = self.files.last().unwrap();
+
+        Box::new(Location {
+            file: frame.path.clone(),
+            element: frame.elements.join("/"),
+            attribute: attr.map(|attr| attr.to_string()),
+            include_stack: self.files.iter().map(|frame| frame.path.clone()).collect(),
+        })
+    }
+
+    pub fn current_file(&self) -> &Path {
+        &self.files.last().unwrap().path
     }
 
+    /// Returns the normalized absolute path of the current launch file.
+    pub fn absolute_file(&self) -> &Path {
+        &self.files.last().unwrap().absolute_path
+    }
+
+    /// Returns the directory containing the current launch file.
     pub fn cwd(&self) -> &Path {
-        self.work_dirs.last().unwrap()
+        self.absolute_file().parent().unwrap_or(Path::new("/"))
     }
 
-    pub fn with_wd<T, F>(&mut self, wd: PathBuf, f: F) -> T
+    pub fn with_file<T, F>(&mut self, frame: FileFrame, f: F) -> T
     where
         F: FnOnce(&mut Self) -> T,
     {
-        self.work_dirs.push(wd);
+        self.files.push(frame);
         let output = f(self);
-        self.work_dirs.pop();
+        self.files.pop();
+        output
+    }
+
+    pub fn with_element<T, F>(&mut self, element: String, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        self.files.last_mut().unwrap().elements.push(element);
+        let output = f(self);
+        self.files.last_mut().unwrap().elements.pop();
         output
     }
 
@@ -356,17 +814,43 @@ impl State {
         F: FnOnce(&mut Self) -> T,
     {
         self.scopes.push(Scope::default());
+        self.env.push_layer();
         let output = f(self);
+        self.env.pop_layer();
         self.scopes.pop().unwrap();
         output
     }
 
-    pub fn contains_var(&self, name: &str) -> bool {
-        self.current_scope().var.contains_key(name)
+    /// Runs the closure with the namespace pushed. The namespace is
+    /// restored afterwards even if the scope is not changed.
+    pub fn with_namespace<T, F>(&mut self, ns: &str, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        let saved = self.current_scope_mut().namespace.clone();
+        self.push_namespace(ns);
+        let output = f(self);
+        self.current_scope_mut().namespace = saved;
+        output
+    }
+
+    /// Pushes the namespace, which lasts until the current scope ends.
+    pub fn push_namespace(&mut self, ns: &str) {
+        let namespace = namespace::push_namespace(self.current_namespace(), ns);
+        self.current_scope_mut().namespace = Some(namespace);
     }
 
-    pub fn contains_env(&self, name: &str) -> bool {
-        self.current_scope().env.contains_key(name)
+    /// Returns the absolute namespace pushed by enclosing groups and
+    /// `<push-ros-namespace>` elements, if any.
+    pub fn current_namespace(&self) -> Option<&str> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.namespace.as_deref())
+    }
+
+    pub fn contains_var(&self, name: &str) -> bool {
+        self.get_var(name).is_some()
     }
 
     pub fn insert_var(&mut self, name: String, value: String) {
@@ -374,56 +858,214 @@ impl State {
     }
 
     pub fn insert_env(&mut self, name: String, value: String) {
-        self.current_scope_mut().var.insert(name, value);
+        self.env.set(name, value);
     }
 
     pub fn get_var(&self, name: &str) -> Option<&str> {
-        let value = self.current_scope().var.get(name)?;
+        let value = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.var.get(name))?;
         Some(value)
     }
 
     pub fn get_env(&self, name: &str) -> Option<&str> {
-        let value = self.current_scope().env.get(name)?;
-        Some(value)
+        self.env.get(name)
+    }
+
+    pub fn remove_env(&mut self, name: &str) -> Option<String> {
+        self.env.unset(name)
+    }
+
+    pub fn current_scope_mut(&mut self) -> &mut Scope {
+        self.scopes.last_mut().unwrap()
     }
+}
 
-    pub fn get_var_or_insert(&mut self, name: &str, default: &str) -> &str {
-        self.current_scope_mut()
-            .var
-            .entry(name.to_string())
-            .or_insert_with(|| default.to_string())
+impl SubstitutionContext for State<'_> {
+    fn env(&self, name: &str) -> Option<String> {
+        self.get_env(name).map(str::to_string)
     }
 
-    pub fn get_env_or_insert(&mut self, name: &str, default: &str) -> &str {
-        self.current_scope_mut()
-            .env
-            .entry(name.to_string())
-            .or_insert_with(|| default.to_string())
+    fn launch_configuration(&self, name: &str) -> Option<String> {
+        self.get_var(name).map(str::to_string)
     }
 
-    pub fn remove_env(&mut self, name: &str) -> Option<String> {
-        self.current_scope_mut().env.remove(name)
+    fn locator(&self) -> &dyn PackageLocator {
+        &*self.options.locator
     }
 
-    pub fn current_scope(&self) -> &Scope {
-        self.scopes.last().unwrap()
+    fn current_file(&self) -> Option<&Path> {
+        self.files.last().map(|frame| frame.absolute_path.as_path())
     }
 
-    pub fn current_scope_mut(&mut self) -> &mut Scope {
-        self.scopes.last_mut().unwrap()
+    fn anon_name(&self, name: &str) -> String {
+        self.anon.borrow_mut().get(name).to_string()
     }
+
+    fn run_command(&self, command: &str) -> std::result::Result<CommandOutput, SubstError> {
+        let env = self.env.effective();
+        self.options.command_runner.run(command, Some(&env))
+    }
+
+    fn registry(&self) -> &SubstitutionRegistry {
+        &self.options.registry
+    }
+}
+
+/// The launch file being parsed and the path to the current element
+/// in it.
+struct FileFrame {
+    /// The path as given by the user or the include element
+    path: PathBuf,
+    /// The normalized absolute path, used by `$(dirname)` and
+    /// `$(filename)`
+    absolute_path: PathBuf,
+    canonical_path: PathBuf,
+    args: Vec<(String, String)>,
+    elements: Vec<String>,
 }
 
+#[derive(Default)]
 struct Scope {
     var: HashMap<String, String>,
-    env: HashMap<String, String>,
+    namespace: Option<String>,
 }
 
-impl Default for Scope {
-    fn default() -> Self {
-        Self {
-            var: HashMap::new(),
-            env: HashMap::new(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use launch_subst::{to_source, CommandPolicy};
+
+    /// Writes the launch files into a new temporary directory.
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("launch-parse-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, text) in files {
+            std::fs::write(dir.join(file), text).unwrap();
         }
+        dir
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = write_files(
+            "cycle",
+            &[
+                (
+                    "a.launch.xml",
+                    r#"<launch>
+  <include file="$(dirname)/b.launch.xml">
+    <arg name="k" value="v"/>
+  </include>
+</launch>"#,
+                ),
+                (
+                    "b.launch.xml",
+                    r#"<launch><include file="$(dirname)/a.launch.xml"/></launch>"#,
+                ),
+            ],
+        );
+        let (a, b) = (dir.join("a.launch.xml"), dir.join("b.launch.xml"));
+
+        let result = load_launch_file(&a, [], &LaunchOptions::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let err = result.unwrap_err();
+        let LaunchError::IncludeCycle { chain, location } = &err else {
+            panic!("expect an include cycle, but get {err}");
+        };
+        assert_eq!(
+            chain,
+            &[
+                IncludeHop {
+                    file: a.clone(),
+                    args: vec![],
+                },
+                IncludeHop {
+                    file: b.clone(),
+                    args: vec![("k".to_string(), "v".to_string())],
+                },
+                IncludeHop {
+                    file: a.clone(),
+                    args: vec![],
+                },
+            ]
+        );
+        assert_eq!(location.file, b);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{location}: include cycle detected: {a} -> {b} [k:=v] -> {a}",
+                a = a.display(),
+                b = b.display()
+            )
+        );
+    }
+
+    #[test]
+    fn include_depth_exceeded() {
+        let include = |next: &str| {
+            format!(r#"<launch><include file="$(dirname)/{next}.launch.xml"/></launch>"#)
+        };
+        let dir = write_files(
+            "depth",
+            &[
+                ("a.launch.xml", &include("b")),
+                ("b.launch.xml", &include("c")),
+                ("c.launch.xml", "<launch/>"),
+            ],
+        );
+
+        let options = |max_include_depth| LaunchOptions {
+            max_include_depth,
+            ..LaunchOptions::default()
+        };
+        let a = dir.join("a.launch.xml");
+        let within = load_launch_file(&a, [], &options(Some(2)));
+        let exceeded = load_launch_file(&a, [], &options(Some(1)));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(within.is_ok());
+        let err = exceeded.unwrap_err();
+        let LaunchError::IncludeDepthExceeded {
+            max_depth,
+            location,
+        } = &err
+        else {
+            panic!("expect the include depth to be exceeded, but get {err}");
+        };
+        assert_eq!(*max_depth, 1);
+        assert_eq!(location.file, dir.join("b.launch.xml"));
+    }
+
+    #[test]
+    fn eval_partial_keeps_unknown_substitutions() {
+        let mut locator = MemoryLocator::new();
+        locator.insert("nav", "/opt/ros");
+        let mut options = LaunchOptions {
+            locator: Box::new(locator),
+            ..LaunchOptions::default()
+        };
+        options.command_runner.policy = CommandPolicy::Allow;
+
+        let preview = |text| {
+            let args = [("robot".to_string(), "r1".to_string())];
+            to_source(&eval_partial(text, args, &options))
+        };
+
+        assert_eq!(
+            preview("$(find-pkg-share nav)/$(var map)"),
+            "/opt/ros/share/nav/$(var map)"
+        );
+        assert_eq!(preview("$(var robot).yaml"), "r1.yaml");
+        assert_eq!(preview("$(command 'echo hi')"), "$(command 'echo hi')");
+        assert_eq!(preview("$(dirname)/x"), "$(dirname)/x");
+        assert_eq!(preview("$(filename)"), "$(filename)");
+        assert_eq!(
+            eval_partial("$(var", [], &options),
+            vec![SubstBlock::text("$(var", 0..5)]
+        );
     }
 }
@@ -0,0 +1,36 @@
+use anyhow::Result;
+pub use launch_subst::{AmentIndexLocator, MemoryLocator, PackageLocator};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Locates packages in a source workspace.
+///
+/// The workspace is scanned once for `package.xml` files on
+/// construction. Both the prefix and the share directory of a package
+/// are the directory containing its manifest file.
+#[derive(Debug, Clone)]
+pub struct WorkspaceLocator {
+    pub packages: HashMap<String, PathBuf>,
+}
+
+impl WorkspaceLocator {
+    pub fn scan<P>(dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let packages = ros_repo::scan_packages(dir)?;
+        Ok(Self { packages })
+    }
+}
+
+impl PackageLocator for WorkspaceLocator {
+    fn find_prefix(&self, pkg: &str) -> Option<PathBuf> {
+        self.packages.get(pkg).cloned()
+    }
+
+    fn find_share(&self, pkg: &str) -> Option<PathBuf> {
+        self.find_prefix(pkg)
+    }
+}
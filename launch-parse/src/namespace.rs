@@ -0,0 +1,76 @@
+//! ROS namespace composition rules.
+
+/// Pushes a namespace onto the base namespace.
+///
+/// An absolute namespace replaces the base namespace, while a
+/// relative namespace is appended to it. The result is always an
+/// absolute namespace without a trailing slash, except the root
+/// namespace `/`.
+pub fn push_namespace(base: Option<&str>, ns: &str) -> String {
+    let joined = match base {
+        Some(base) if !ns.starts_with('/') => format!("{base}/{ns}"),
+        _ => ns.to_string(),
+    };
+    normalize(&joined)
+}
+
+/// Computes the fully qualified namespace of a node.
+///
+/// A node without a `namespace` attribute is placed in the base
+/// namespace, or in the root namespace if no namespace was pushed.
+pub fn node_namespace(base: Option<&str>, ns: Option<&str>) -> String {
+    match ns {
+        Some(ns) => push_namespace(base, ns),
+        None => normalize(base.unwrap_or("/")),
+    }
+}
+
+fn normalize(ns: &str) -> String {
+    let mut out = String::with_capacity(ns.len() + 1);
+
+    for token in ns.split('/').filter(|token| !token.is_empty()) {
+        out.push('/');
+        out.push_str(token);
+    }
+
+    if out.is_empty() {
+        out.push('/');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_namespaces() {
+        assert_eq!(push_namespace(None, "robot"), "/robot");
+        assert_eq!(push_namespace(Some("/robot"), "arm"), "/robot/arm");
+        assert_eq!(
+            push_namespace(Some("/robot"), "arm/left"),
+            "/robot/arm/left"
+        );
+        assert_eq!(push_namespace(Some("/robot"), "/sim"), "/sim");
+        assert_eq!(push_namespace(Some("/robot"), "/"), "/");
+        assert_eq!(push_namespace(Some("/"), "arm"), "/arm");
+        assert_eq!(push_namespace(None, "/"), "/");
+        assert_eq!(push_namespace(Some("/robot/"), "arm/"), "/robot/arm");
+        assert_eq!(
+            push_namespace(Some("//robot"), "arm//left"),
+            "/robot/arm/left"
+        );
+        assert_eq!(push_namespace(Some("/robot"), ""), "/robot");
+    }
+
+    #[test]
+    fn node_namespaces() {
+        assert_eq!(node_namespace(None, None), "/");
+        assert_eq!(node_namespace(Some("/robot/"), None), "/robot");
+        assert_eq!(node_namespace(None, Some("arm")), "/arm");
+        assert_eq!(node_namespace(Some("/robot"), Some("arm")), "/robot/arm");
+        assert_eq!(node_namespace(Some("/robot"), Some("/arm")), "/arm");
+        assert_eq!(node_namespace(Some("/robot"), Some("/")), "/");
+    }
+}
@@ -0,0 +1,44 @@
+use crate::locator::{AmentIndexLocator, PackageLocator};
+use launch_subst::{CommandRunner, SubstitutionRegistry};
+use std::collections::HashMap;
+
+/// Options to load launch files.
+pub struct LaunchOptions {
+    /// Locates packages for `$(find)`, `$(find-pkg-share)` and
+    /// `$(find-pkg-prefix)` substitutions.
+    pub locator: Box<dyn PackageLocator>,
+
+    /// The maximum number of nested includes below the top-level
+    /// launch file. It is unlimited if `None`.
+    pub max_include_depth: Option<usize>,
+
+    /// The base environment of launched processes. The environment of
+    /// the current process is used if `None`.
+    pub environment: Option<HashMap<String, String>>,
+
+    /// The seed to generate `$(anon)` names. Names are random across
+    /// loads if `None`.
+    pub anon_seed: Option<u64>,
+
+    /// Runs commands for `$(command)` substitutions. No commands are
+    /// allowed to run by default.
+    pub command_runner: CommandRunner,
+
+    /// Evaluates substitutions not built into ROS launch, e.g.
+    /// `$(robot-serial)`. Unregistered ones are reported as
+    /// unsupported.
+    pub registry: SubstitutionRegistry,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            locator: Box::new(AmentIndexLocator::from_env()),
+            max_include_depth: None,
+            environment: None,
+            anon_seed: None,
+            command_runner: CommandRunner::default(),
+            registry: SubstitutionRegistry::new(),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Loading of ROS 2 parameter files.
+
+use crate::context::ParamValue;
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+
+const PARAMS_KEY: &str = "ros__parameters";
+
+/// Loads the parameters that apply to the node from a parameter file.
+///
+/// The file maps node names to `ros__parameters` tables. A node name
+/// may be nested in namespace keys and may contain the wildcards `*`
+/// and `**`. Nested tables are flattened into dotted parameter names.
+/// Parameters are returned in file order.
+pub fn load_param_file(
+    path: &Path,
+    namespace: &str,
+    name: Option<&str>,
+) -> Result<Vec<(String, Value)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    parse_params(&text, namespace, name)
+}
+
+/// Parses the text of a parameter file like `load_param_file`.
+fn parse_params(
+    text: &str,
+    namespace: &str,
+    name: Option<&str>,
+) -> Result<Vec<(String, Value)>, String> {
+    let root: Value = serde_yaml::from_str(text).map_err(|err| err.to_string())?;
+
+    let Value::Mapping(root) = root else {
+        return Err("expect a mapping from node names to parameters".to_string());
+    };
+
+    let node_tokens: Vec<Option<&str>> = namespace
+        .split('/')
+        .filter(|token| !token.is_empty())
+        .map(Some)
+        .chain([name])
+        .collect();
+
+    let mut params = vec![];
+    collect_nodes(&root, &mut vec![], &node_tokens, &mut params)?;
+    Ok(params)
+}
+
+/// Walks the keys above `ros__parameters` and collects the parameters
+/// of matching node names.
+fn collect_nodes<'a>(
+    map: &'a Mapping,
+    prefix: &mut Vec<&'a str>,
+    node_tokens: &[Option<&str>],
+    params: &mut Vec<(String, Value)>,
+) -> Result<(), String> {
+    for (key, value) in map {
+        let Some(key) = key.as_str() else {
+            return Err(format!("expect a string key, but get {key:?}"));
+        };
+
+        if key == PARAMS_KEY {
+            let Value::Mapping(table) = value else {
+                return Err(format!("expect '{PARAMS_KEY}' to be a mapping"));
+            };
+
+            if match_node_name(prefix, node_tokens) {
+                flatten_params(table, "", params)?;
+            }
+            continue;
+        }
+
+        let Value::Mapping(child) = value else {
+            return Err(format!("expect '{key}' to be a mapping"));
+        };
+
+        let len = prefix.len();
+        prefix.extend(key.split('/').filter(|token| !token.is_empty()));
+        collect_nodes(child, prefix, node_tokens, params)?;
+        prefix.truncate(len);
+    }
+
+    Ok(())
+}
+
+fn flatten_params(
+    table: &Mapping,
+    prefix: &str,
+    params: &mut Vec<(String, Value)>,
+) -> Result<(), String> {
+    for (key, value) in table {
+        let Some(key) = key.as_str() else {
+            return Err(format!("expect a string parameter name, but get {key:?}"));
+        };
+        let name = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match value {
+            Value::Mapping(child) => flatten_params(child, &name, params)?,
+            _ => params.push((name, value.clone())),
+        }
+    }
+
+    Ok(())
+}
+
+/// Matches a node name pattern against the tokens of the fully
+/// qualified node name. `*` matches exactly one token and `**` matches
+/// zero or more tokens. A node without a name is only matched by
+/// wildcards.
+fn match_node_name(pattern: &[&str], node: &[Option<&str>]) -> bool {
+    match (pattern.split_first(), node.split_first()) {
+        (None, None) => true,
+        (Some((&"**", rest)), _) => {
+            match_node_name(rest, node)
+                || (!node.is_empty() && match_node_name(pattern, &node[1..]))
+        }
+        (Some((&"*", rest)), Some((_, node_rest))) => match_node_name(rest, node_rest),
+        (Some((token, rest)), Some((Some(node_token), node_rest))) => {
+            token == node_token && match_node_name(rest, node_rest)
+        }
+        _ => false,
+    }
+}
+
+/// Infers the type of a parameter value given as text.
+///
+/// As `launch_ros` does, the text is read as YAML. Scalars and
+/// homogeneous lists keep the YAML type, while text that is not valid
+/// YAML, null and mappings stay strings. A list mixing item types is
+/// an error.
+pub fn infer_value(text: &str) -> Result<ParamValue, String> {
+    match serde_yaml::from_str::<Value>(text) {
+        Ok(Value::Null | Value::Mapping(_) | Value::Tagged(_)) | Err(_) => {
+            Ok(ParamValue::String(text.to_string()))
+        }
+        Ok(value) => from_yaml(&value),
+    }
+}
+
+/// Splits the text by the separator into a list value.
+///
+/// Each item is trimmed and inferred as a scalar, as `launch_ros` does,
+/// so `1,2` gives an integer list rather than a list of strings. Items
+/// that are not scalars in YAML stay strings. A list mixing item types
+/// is an error.
+pub fn infer_list(text: &str, sep: &str) -> Result<ParamValue, String> {
+    let values: Vec<Value> = text
+        .split(sep)
+        .map(str::trim)
+        .map(|item| match serde_yaml::from_str::<Value>(item) {
+            Ok(value @ (Value::Bool(_) | Value::Number(_) | Value::String(_))) => value,
+            _ => Value::String(item.to_string()),
+        })
+        .collect();
+
+    from_yaml_list(&values)
+}
+
+/// Converts a value in a parameter file to a typed value.
+pub fn from_yaml(value: &Value) -> Result<ParamValue, String> {
+    let value = match value {
+        Value::Bool(value) => ParamValue::Bool(*value),
+        Value::Number(number) => match (number.as_i64(), number.as_f64()) {
+            (Some(value), _) => ParamValue::Integer(value),
+            (None, Some(value)) if !number.is_u64() => ParamValue::Double(value),
+            _ => return Err(format!("the integer {number} is out of range")),
+        },
+        Value::String(value) => ParamValue::String(value.clone()),
+        Value::Sequence(items) => from_yaml_list(items)?,
+        Value::Null => return Err("the value is null".to_string()),
+        Value::Mapping(_) | Value::Tagged(_) => {
+            return Err(format!("unsupported parameter value {value:?}"))
+        }
+    };
+    Ok(value)
+}
+
+fn from_yaml_list(items: &[Value]) -> Result<ParamValue, String> {
+    let values: Vec<ParamValue> = items
+        .iter()
+        .map(|item| match item {
+            Value::Sequence(_) => Err("nested lists are not supported".to_string()),
+            _ => from_yaml(item),
+        })
+        .collect::<Result<_, _>>()?;
+
+    macro_rules! collect_all {
+        ($variant:ident) => {
+            values
+                .iter()
+                .map(|value| match value {
+                    ParamValue::$variant(value) => Some(value.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+        };
+    }
+
+    let value = if let Some(values) = collect_all!(String) {
+        ParamValue::StringArray(values)
+    } else if let Some(values) = collect_all!(Bool) {
+        ParamValue::BoolArray(values)
+    } else if let Some(values) = collect_all!(Integer) {
+        ParamValue::IntegerArray(values)
+    } else if let Some(values) = collect_all!(Double) {
+        ParamValue::DoubleArray(values)
+    } else {
+        return Err("the items of a list must have the same type".to_string());
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_name(pattern: &str, node: &[Option<&str>]) -> bool {
+        let pattern: Vec<_> = pattern
+            .split('/')
+            .filter(|token| !token.is_empty())
+            .collect();
+        match_node_name(&pattern, node)
+    }
+
+    #[test]
+    fn match_wildcards() {
+        let node = [Some("robot"), Some("arm"), Some("driver")];
+
+        assert!(node_name("/robot/arm/driver", &node));
+        assert!(!node_name("/robot/driver", &node));
+        assert!(node_name("/**", &node));
+        assert!(node_name("/**/driver", &node));
+        assert!(node_name("/robot/**/driver", &node));
+        assert!(node_name("/robot/arm/**/driver", &node));
+        assert!(!node_name("/**/arm", &node));
+
+        // `*` matches exactly one token.
+        assert!(node_name("/robot/*/driver", &node));
+        assert!(node_name("/*/*/driver", &node));
+        assert!(!node_name("/*/driver", &node));
+        assert!(!node_name("/robot/arm/*/driver", &node));
+
+        // A node without a name is only matched by wildcards.
+        let unnamed = [Some("robot"), None];
+        assert!(node_name("/robot/*", &unnamed));
+        assert!(node_name("/**", &unnamed));
+        assert!(!node_name("/robot/driver", &unnamed));
+        assert!(!node_name("/robot", &unnamed));
+    }
+
+    #[test]
+    fn collect_nested_nodes() {
+        let text = r#"
+/**:
+  ros__parameters:
+    use_sim_time: true
+robot:
+  arm:
+    driver:
+      ros__parameters:
+        rate: 10
+        limits: { max: 1.5 }
+  /*/driver:
+    ros__parameters:
+      name: any
+/robot/camera:
+  ros__parameters:
+    rate: 30
+"#;
+
+        let names = |namespace, name| -> Vec<String> {
+            parse_params(text, namespace, name)
+                .unwrap()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        };
+
+        assert_eq!(
+            names("/robot/arm", Some("driver")),
+            ["use_sim_time", "rate", "limits.max", "name"]
+        );
+        assert_eq!(names("/robot", Some("camera")), ["use_sim_time", "rate"]);
+        assert_eq!(names("/", Some("driver")), ["use_sim_time"]);
+        assert_eq!(names("/robot/arm", None), ["use_sim_time"]);
+        assert!(parse_params("node: 1", "/", Some("node")).is_err());
+    }
+
+    #[test]
+    fn infer_list_items() {
+        assert_eq!(
+            infer_list("1, 2 ,3", ","),
+            Ok(ParamValue::IntegerArray(vec![1, 2, 3]))
+        );
+        assert_eq!(
+            infer_list(" x ; y", ";"),
+            Ok(ParamValue::StringArray(vec![
+                "x".to_string(),
+                "y".to_string()
+            ]))
+        );
+        assert_eq!(
+            infer_list("true,False", ","),
+            Ok(ParamValue::BoolArray(vec![true, false]))
+        );
+        assert!(infer_list("1,x", ",").is_err());
+    }
+}
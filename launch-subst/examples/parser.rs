@@ -1,14 +1,22 @@
 use clap::Parser;
-use launch_subst::parse;
+use launch_subst::{parse_with_dialect, Dialect};
 
 #[derive(Parser)]
 struct Opts {
+    /// Parse in the ROS 1 roslaunch syntax.
+    #[clap(long)]
+    pub ros1: bool,
     pub expr: String,
 }
 
 fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
-    let output = parse(&opts.expr)?;
+    let dialect = if opts.ros1 {
+        Dialect::Ros1
+    } else {
+        Dialect::Ros2
+    };
+    let output = parse_with_dialect(&opts.expr, dialect)?;
     dbg!(output);
     Ok(())
 }
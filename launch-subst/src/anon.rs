@@ -0,0 +1,88 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Generates anonymous names for `$(anon)` substitutions.
+///
+/// A name is generated once for each distinct argument and reused
+/// afterwards, so that `$(anon foo)` evaluates to the same name within
+/// a launch session. Generated names are valid ROS node names: the
+/// argument with invalid characters replaced by underscores, followed
+/// by a random suffix.
+///
+/// The generator is randomly seeded by default. Use
+/// [`AnonNames::with_seed`] to make the names reproducible.
+#[derive(Debug, Clone)]
+pub struct AnonNames {
+    state: u64,
+    names: HashMap<String, String>,
+}
+
+impl AnonNames {
+    /// Creates a randomly seeded generator.
+    pub fn new() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        if let Ok(elapsed) = SystemTime::now().duration_since(UNIX_EPOCH) {
+            hasher.write_u128(elapsed.as_nanos());
+        }
+        hasher.write_u32(std::process::id());
+        Self::with_seed(hasher.finish())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            state: seed,
+            names: HashMap::new(),
+        }
+    }
+
+    /// Returns the anonymous name for the argument, generating one if
+    /// it is not asked before.
+    pub fn get(&mut self, name: &str) -> &str {
+        if !self.names.contains_key(name) {
+            let anon = format!("{}_{:016x}", sanitize(name), self.next_u64());
+            self.names.insert(name.to_string(), anon);
+        }
+        &self.names[name]
+    }
+
+    /// SplitMix64
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl Default for AnonNames {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Turns the text into the base of a valid ROS name, which consists of
+/// alphanumerics and underscores and does not start with a digit.
+fn sanitize(name: &str) -> String {
+    let mut base: String = name
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '_' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if base.is_empty() {
+        base.push_str("anon");
+    } else if base.starts_with(|ch: char| ch.is_ascii_digit()) {
+        base.insert_str(0, "anon_");
+    }
+
+    base
+}
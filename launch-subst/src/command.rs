@@ -0,0 +1,383 @@
+//! Running commands for `$(command)` substitutions.
+
+use crate::{error::SubstError, package::find_in_path};
+use std::{
+    collections::HashMap,
+    env,
+    io::Read,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The output of a command run by `$(command)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// The exit code, or `None` if the command is terminated by a
+    /// signal.
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Decides which commands may run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CommandPolicy {
+    /// Run any command.
+    Allow,
+
+    /// Run no commands.
+    #[default]
+    Deny,
+
+    /// Run only the listed programs. An entry is an absolute path, e.g.
+    /// `/opt/ros/bin/xacro`, or a bare name, e.g. `xacro`, which is
+    /// searched in the `PATH` of the current process. The program in
+    /// the command is resolved the same way and run by its resolved
+    /// path, so that the launch file cannot pick another program by
+    /// setting `PATH` or giving a relative path.
+    Allowlist(Vec<String>),
+}
+
+impl CommandPolicy {
+    pub fn allows(&self, program: &str) -> bool {
+        self.resolve(program).is_some()
+    }
+
+    /// Returns the path to run the program with, or `None` if the
+    /// program is not allowed.
+    pub fn resolve(&self, program: &str) -> Option<PathBuf> {
+        match self {
+            CommandPolicy::Allow => Some(PathBuf::from(program)),
+            CommandPolicy::Deny => None,
+            CommandPolicy::Allowlist(programs) => {
+                let search_path = env::var_os("PATH").unwrap_or_default();
+                let resolve = |name: &str| {
+                    if Path::new(name).is_absolute() {
+                        Some(PathBuf::from(name))
+                    } else if !name.contains(MAIN_SEPARATOR) {
+                        find_in_path(name, &search_path)
+                    } else {
+                        None
+                    }
+                };
+
+                let program = resolve(program)?;
+                programs
+                    .iter()
+                    .filter_map(|allowed| resolve(allowed))
+                    .any(|allowed| allowed == program)
+                    .then_some(program)
+            }
+        }
+    }
+}
+
+/// Runs commands for `$(command)` under a policy.
+///
+/// The command line is split into words as ROS does and run without a
+/// shell. Its stdin is closed, and stdout and stderr are captured. The
+/// command runs in its own process group, which is killed if the
+/// command or the processes it leaves holding its output run longer
+/// than the timeout.
+#[derive(Debug, Clone, Default)]
+pub struct CommandRunner {
+    pub policy: CommandPolicy,
+
+    /// The longest time a command may run. It is unlimited if `None`.
+    pub timeout: Option<Duration>,
+
+    /// The working directory of commands. The current directory is used
+    /// if `None`.
+    pub cwd: Option<PathBuf>,
+}
+
+impl CommandRunner {
+    /// Runs the command line. If `env` is given, the command runs with
+    /// exactly these environment variables. Otherwise, it inherits the
+    /// environment of the current process.
+    pub fn run(
+        &self,
+        command: &str,
+        env: Option<&HashMap<String, String>>,
+    ) -> Result<CommandOutput, SubstError> {
+        let fail = |reason: String| SubstError::Command {
+            command: command.to_string(),
+            reason,
+        };
+
+        let argv = split_command(command).map_err(|reason| fail(reason.to_string()))?;
+        let Some((program, args)) = argv.split_first() else {
+            return Err(fail("the command is empty".to_string()));
+        };
+
+        let Some(program) = self.policy.resolve(program) else {
+            return Err(SubstError::CommandDenied {
+                command: command.to_string(),
+            });
+        };
+
+        let mut cmd = Command::new(program);
+        cmd.args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(env) = env {
+            cmd.env_clear().envs(env);
+        }
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+
+        let mut child = cmd.spawn().map_err(|err| fail(err.to_string()))?;
+
+        // Read the pipes in the background so that the command does not
+        // block on a full pipe while we wait for it.
+        let (done_tx, done_rx) = mpsc::channel();
+        let read_pipe = |pipe: Option<Box<dyn Read + Send>>| {
+            let done_tx = done_tx.clone();
+            thread::spawn(move || {
+                let mut buf = vec![];
+                if let Some(mut pipe) = pipe {
+                    let _ = pipe.read_to_end(&mut buf);
+                }
+                let _ = done_tx.send(());
+                String::from_utf8_lossy(&buf).into_owned()
+            })
+        };
+        let stdout = read_pipe(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+        let stderr = read_pipe(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+        drop(done_tx);
+
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let mut timed_out = false;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                    kill_process_group(&mut child);
+                    timed_out = true;
+                    break child.wait();
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(err) => {
+                    kill_process_group(&mut child);
+                    let _ = child.wait();
+                    break Err(err);
+                }
+            }
+        };
+
+        // Processes left by the command, e.g. `sh -c 'sleep 100 &'`, may
+        // keep the pipes open after it exits. They are bound by the same
+        // deadline.
+        if !timed_out && status.is_ok() {
+            for _ in 0..2 {
+                let finished = match deadline {
+                    Some(deadline) => {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        !matches!(
+                            done_rx.recv_timeout(remaining),
+                            Err(RecvTimeoutError::Timeout)
+                        )
+                    }
+                    None => {
+                        let _ = done_rx.recv();
+                        true
+                    }
+                };
+
+                if !finished {
+                    kill_process_group(&mut child);
+                    timed_out = true;
+                    break;
+                }
+            }
+        }
+
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        let status = status.map_err(|err| fail(err.to_string()))?;
+
+        if timed_out {
+            let timeout = self.timeout.unwrap();
+            return Err(fail(format!("timed out after {timeout:?}")));
+        }
+
+        Ok(CommandOutput {
+            status: status.code(),
+            stdout,
+            stderr,
+        })
+    }
+}
+
+/// Kills the process group led by the child, or only the child on
+/// platforms without process groups.
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        // SAFETY: kill() has no memory safety requirements. The group
+        // is created by the child on spawn.
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = child.kill();
+    }
+}
+
+/// Splits a command line into words like a POSIX shell, without
+/// expansions. ROS does the same with Python's `shlex.split()`.
+pub fn split_command(command: &str) -> Result<Vec<String>, &'static str> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            ch if ch.is_whitespace() => {
+                words.extend(word.take());
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => word.push(ch),
+                        None => return Err("no closing quotation"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(ch @ ('\\' | '"' | '$' | '`')) => word.push(ch),
+                            Some('\n') => {}
+                            Some(ch) => {
+                                word.push('\\');
+                                word.push(ch);
+                            }
+                            None => return Err("no closing quotation"),
+                        },
+                        Some(ch) => word.push(ch),
+                        None => return Err("no closing quotation"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(ch) => word.get_or_insert_with(String::new).push(ch),
+                None => return Err("no escaped character"),
+            },
+            ch => word.get_or_insert_with(String::new).push(ch),
+        }
+    }
+
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_words() {
+        assert_eq!(
+            split_command(r#"echo 'a b' "c \"d\"" e\ f"#),
+            Ok(vec![
+                "echo".to_string(),
+                "a b".to_string(),
+                r#"c "d""#.to_string(),
+                "e f".to_string(),
+            ])
+        );
+        assert!(split_command("echo 'a").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn allowlist_resolves_programs() {
+        let policy = CommandPolicy::Allowlist(vec!["sh".to_string()]);
+        let sh = find_in_path("sh", &env::var_os("PATH").unwrap()).unwrap();
+
+        assert_eq!(policy.resolve("sh"), Some(sh.clone()));
+        assert_eq!(policy.resolve(sh.to_str().unwrap()), Some(sh));
+        assert!(!policy.allows("/tmp/evil/sh"));
+        assert!(!policy.allows("./sh"));
+        assert!(!policy.allows("bash"));
+
+        let policy = CommandPolicy::Allowlist(vec!["/opt/ros/bin/xacro".to_string()]);
+        assert!(policy.allows("/opt/ros/bin/xacro"));
+        assert!(!policy.allows("xacro"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn allowlist_ignores_launch_path() {
+        let dir = env::temp_dir().join(format!("launch-subst-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake = dir.join("echo");
+        std::fs::write(&fake, "#!/bin/sh\necho evil\n").unwrap();
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let runner = CommandRunner {
+            policy: CommandPolicy::Allowlist(vec!["echo".to_string()]),
+            ..CommandRunner::default()
+        };
+        let env = HashMap::from([("PATH".to_string(), dir.display().to_string())]);
+        let output = runner.run("echo hello", Some(&env));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(output.unwrap().stdout, "hello\n");
+    }
+
+    #[test]
+    fn deny_by_default() {
+        assert!(matches!(
+            CommandRunner::default().run("echo hello", None),
+            Err(SubstError::CommandDenied { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timeout() {
+        let runner = CommandRunner {
+            policy: CommandPolicy::Allow,
+            timeout: Some(Duration::from_millis(200)),
+            ..CommandRunner::default()
+        };
+
+        let output = runner.run("sh -c 'echo hello'", None).unwrap();
+        assert_eq!(output.status, Some(0));
+        assert_eq!(output.stdout, "hello\n");
+
+        for command in ["sleep 30", "sh -c 'sleep 30 &'"] {
+            let start = Instant::now();
+            let result = runner.run(command, None);
+            assert!(
+                matches!(result, Err(SubstError::Command { .. })),
+                "{command}"
+            );
+            assert!(start.elapsed() < Duration::from_secs(10), "{command}");
+        }
+    }
+}
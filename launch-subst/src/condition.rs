@@ -0,0 +1,44 @@
+//! Helpers for condition substitutions such as `$(not)` and
+//! `$(equals)`.
+
+/// Reads a condition value. As ROS does, `true` and `1` are true, and
+/// `false` and `0` are false, ignoring letter case. Other values are
+/// not valid conditions.
+pub fn parse_bool(text: &str) -> Option<bool> {
+    if text == "1" || text.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if text == "0" || text.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Compares two substituted values for `$(equals)` and
+/// `$(not-equals)`.
+///
+/// Values that both read as booleans (`true` or `false`, ignoring
+/// letter case) or both read as numbers are compared as such, so that
+/// `True` equals `true` and `1.0` equals `1`. Other values are compared
+/// as strings.
+pub fn values_equal(lhs: &str, rhs: &str) -> bool {
+    let as_bool = |text: &str| {
+        if text.eq_ignore_ascii_case("true") {
+            Some(true)
+        } else if text.eq_ignore_ascii_case("false") {
+            Some(false)
+        } else {
+            None
+        }
+    };
+
+    if let (Some(lhs), Some(rhs)) = (as_bool(lhs), as_bool(rhs)) {
+        return lhs == rhs;
+    }
+
+    if let (Ok(lhs), Ok(rhs)) = (lhs.trim().parse::<f64>(), rhs.trim().parse::<f64>()) {
+        return lhs == rhs;
+    }
+
+    lhs == rhs
+}
@@ -0,0 +1,152 @@
+use crate::{
+    anon::AnonNames,
+    command::{CommandOutput, CommandRunner},
+    error::SubstError,
+    package::{AmentIndexLocator, PackageLocator},
+    registry::SubstitutionRegistry,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Provides what substitutions read from the launch host.
+///
+/// [`Substitution::eval`](crate::Substitution::eval) is generic over
+/// this trait, so that the same evaluation is shared by launch file
+/// loaders and other tools.
+pub trait SubstitutionContext {
+    /// Returns the value of an environment variable for `$(env)` and
+    /// `$(optenv)`.
+    fn env(&self, name: &str) -> Option<String>;
+
+    /// Returns the value of a launch configuration for `$(arg)`,
+    /// `$(var)` and identifiers in `$(eval)`.
+    fn launch_configuration(&self, name: &str) -> Option<String>;
+
+    /// Returns the locator used by `$(find-pkg-share)` and related
+    /// substitutions.
+    fn locator(&self) -> &dyn PackageLocator;
+
+    /// Returns the absolute path of the launch file being evaluated for
+    /// `$(dirname)` and `$(filename)`.
+    fn current_file(&self) -> Option<&Path>;
+
+    /// Returns the anonymous name for `$(anon)`. The same argument must
+    /// give the same name within a launch session.
+    fn anon_name(&self, name: &str) -> String;
+
+    /// Runs the command line of `$(command)` and returns its output.
+    fn run_command(&self, command: &str) -> Result<CommandOutput, SubstError>;
+
+    /// Returns the handlers of substitutions not built into ROS launch.
+    fn registry(&self) -> &SubstitutionRegistry;
+}
+
+impl<T> SubstitutionContext for &T
+where
+    T: SubstitutionContext + ?Sized,
+{
+    fn env(&self, name: &str) -> Option<String> {
+        (**self).env(name)
+    }
+
+    fn launch_configuration(&self, name: &str) -> Option<String> {
+        (**self).launch_configuration(name)
+    }
+
+    fn locator(&self) -> &dyn PackageLocator {
+        (**self).locator()
+    }
+
+    fn current_file(&self) -> Option<&Path> {
+        (**self).current_file()
+    }
+
+    fn anon_name(&self, name: &str) -> String {
+        (**self).anon_name(name)
+    }
+
+    fn run_command(&self, command: &str) -> Result<CommandOutput, SubstError> {
+        (**self).run_command(command)
+    }
+
+    fn registry(&self) -> &SubstitutionRegistry {
+        (**self).registry()
+    }
+}
+
+/// A context backed by the current process.
+///
+/// Environment variables are read from the process, packages are
+/// located with `AMENT_PREFIX_PATH`, and commands are denied unless the
+/// policy of `command_runner` is changed. Launch configurations and the
+/// current file are given by the user.
+#[derive(Debug)]
+pub struct ProcessContext {
+    pub locator: AmentIndexLocator,
+    pub command_runner: CommandRunner,
+    pub registry: SubstitutionRegistry,
+    pub launch_configurations: HashMap<String, String>,
+    pub current_file: Option<PathBuf>,
+    anon: RefCell<AnonNames>,
+}
+
+impl ProcessContext {
+    pub fn new() -> Self {
+        Self {
+            locator: AmentIndexLocator::from_env(),
+            command_runner: CommandRunner::default(),
+            registry: SubstitutionRegistry::new(),
+            launch_configurations: HashMap::new(),
+            current_file: None,
+            anon: RefCell::new(AnonNames::new()),
+        }
+    }
+
+    /// Replaces the generator of `$(anon)` names.
+    pub fn with_anon_names(self, anon: AnonNames) -> Self {
+        Self {
+            anon: RefCell::new(anon),
+            ..self
+        }
+    }
+}
+
+impl Default for ProcessContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubstitutionContext for ProcessContext {
+    fn env(&self, name: &str) -> Option<String> {
+        env::var(name).ok()
+    }
+
+    fn launch_configuration(&self, name: &str) -> Option<String> {
+        self.launch_configurations.get(name).cloned()
+    }
+
+    fn locator(&self) -> &dyn PackageLocator {
+        &self.locator
+    }
+
+    fn current_file(&self) -> Option<&Path> {
+        self.current_file.as_deref()
+    }
+
+    fn anon_name(&self, name: &str) -> String {
+        self.anon.borrow_mut().get(name).to_string()
+    }
+
+    fn run_command(&self, command: &str) -> Result<CommandOutput, SubstError> {
+        self.command_runner.run(command, None)
+    }
+
+    fn registry(&self) -> &SubstitutionRegistry {
+        &self.registry
+    }
+}
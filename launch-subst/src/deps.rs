@@ -0,0 +1,129 @@
+//! Dependency analysis of parsed substitutions.
+
+use crate::{
+    eval,
+    parse::Dialect,
+    render::to_source,
+    types::{roslaunch_substitution, SubstBlock, Substitution},
+};
+use std::collections::BTreeSet;
+
+/// An input that a substitution reads when it is evaluated.
+///
+/// A name computed by nested substitutions is given as its source text,
+/// e.g. `$(arg robot)_description`. The dependencies of the nested
+/// substitutions are listed separately.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Dependency {
+    /// A launch configuration read by `$(arg)`, `$(var)`,
+    /// `$(launch_configuration)` or an identifier in `$(eval)`
+    LaunchConfiguration(String),
+    /// An environment variable read by `$(env)` or `$(optenv)`
+    Env(String),
+    /// A package located by `$(find-pkg-share)`, `$(exec-in-pkg)` and
+    /// the like
+    Package(String),
+    /// An executable searched in `PATH` by `$(find-exec)`
+    Executable(String),
+    /// A command line run by `$(command)`
+    Command(String),
+    /// A parameter read by `$(param)`
+    Param(String),
+    /// The path of the launch file, read by `$(dirname)` and
+    /// `$(filename)`
+    CurrentFile,
+    /// A substitution unknown to the parser
+    Unknown(String),
+}
+
+/// Lists the dependencies of the blocks without evaluating them.
+pub fn dependencies(blocks: &[SubstBlock]) -> BTreeSet<Dependency> {
+    let mut deps = BTreeSet::new();
+    collect_blocks(blocks, &mut deps);
+    deps
+}
+
+impl Substitution<'_> {
+    /// Lists the dependencies of the substitution without evaluating
+    /// it.
+    pub fn dependencies(&self) -> BTreeSet<Dependency> {
+        let mut deps = BTreeSet::new();
+        collect_subst(self, &mut deps);
+        deps
+    }
+}
+
+fn collect_blocks(blocks: &[SubstBlock], deps: &mut BTreeSet<Dependency>) {
+    for block in blocks {
+        if let SubstBlock::Substitution { subst, .. } = block {
+            collect_subst(subst, deps);
+        }
+    }
+}
+
+fn collect_subst(subst: &Substitution, deps: &mut BTreeSet<Dependency>) {
+    for arg in subst.args() {
+        collect_blocks(arg, deps);
+    }
+
+    match subst {
+        Substitution::Env { variable } | Substitution::OptEnv { variable, .. } => {
+            deps.insert(Dependency::Env(to_source(variable)));
+        }
+        Substitution::Find { pkg }
+        | Substitution::FindPkgShare { pkg }
+        | Substitution::FindPkgPrefix { pkg }
+        | Substitution::ExecInPkg { pkg, .. } => {
+            deps.insert(Dependency::Package(to_source(pkg)));
+        }
+        Substitution::Arg { name }
+        | Substitution::Var { name }
+        | Substitution::LaunchConfiguration { name } => {
+            deps.insert(Dependency::LaunchConfiguration(to_source(name)));
+        }
+        Substitution::FindExec { name } => {
+            deps.insert(Dependency::Executable(to_source(name)));
+        }
+        Substitution::Command { command, .. } => {
+            deps.insert(Dependency::Command(to_source(command)));
+        }
+        Substitution::Param { name } => {
+            deps.insert(Dependency::Param(to_source(name)));
+        }
+        Substitution::Eval { expr, dialect } => {
+            // Identifiers are known only if the expression is literal.
+            // Invalid expressions have no dependencies to report.
+            if let [SubstBlock::Text { text: expr, .. }] = &expr[..] {
+                if let Ok(names) = eval::identifiers(expr) {
+                    deps.extend(names.into_iter().map(Dependency::LaunchConfiguration));
+                }
+
+                // Calls to roslaunch functions in ROS 1 expressions
+                // depend on what the substitutions of the same names do.
+                let calls = match dialect {
+                    Dialect::Ros1 => eval::literal_calls(expr),
+                    Dialect::Ros2 => Ok(vec![]),
+                };
+                if let Ok(calls) = calls {
+                    for (name, args) in calls {
+                        if let Some(Ok(subst)) = roslaunch_substitution(&name, &args) {
+                            collect_subst(&subst, deps);
+                        }
+                    }
+                }
+            }
+        }
+        Substitution::DirName | Substitution::FileName => {
+            deps.insert(Dependency::CurrentFile);
+        }
+        Substitution::Other { command, .. } => {
+            deps.insert(Dependency::Unknown(command.to_string()));
+        }
+        Substitution::Anon { .. }
+        | Substitution::Not { .. }
+        | Substitution::And { .. }
+        | Substitution::Or { .. }
+        | Substitution::Equals { .. }
+        | Substitution::NotEquals { .. } => {}
+    }
+}
@@ -0,0 +1,45 @@
+use crate::eval::EvalError;
+
+/// The error returned when a substitution cannot be evaluated.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SubstError {
+    #[error(r#"the environment variable "{name}" is not set"#)]
+    UndefinedEnv { name: String },
+
+    #[error(r#"the launch configuration "{name}" is not defined"#)]
+    UndefinedLaunchConfiguration { name: String },
+
+    #[error(r#"unable to find the package "{pkg}""#)]
+    PackageNotFound { pkg: String },
+
+    #[error(r#"unable to find the executable "{name}""#)]
+    ExecutableNotFound { name: String },
+
+    #[error(r#"expect a condition "true", "false", "1" or "0", but get "{value}""#)]
+    InvalidCondition { value: String },
+
+    #[error(r#"unable to evaluate the expression "{expr}": {source}"#)]
+    Eval { expr: String, source: EvalError },
+
+    #[error("the current launch file is unknown")]
+    UnknownFile,
+
+    #[error(r#"the command "{command}" failed: {reason}"#)]
+    Command { command: String, reason: String },
+
+    #[error(r#"running the command "{command}" is not allowed"#)]
+    CommandDenied { command: String },
+
+    #[error(r#"the substitution "{command}" expects {expected}, but get {count}"#)]
+    InvalidArgumentCount {
+        command: String,
+        expected: String,
+        count: usize,
+    },
+
+    #[error(r#"the substitution "{command}" failed: {reason}"#)]
+    Custom { command: String, reason: String },
+
+    #[error(r#"the substitution "{name}" is not supported"#)]
+    Unsupported { name: String },
+}
@@ -0,0 +1,925 @@
+//! Evaluation of `$(eval)` expressions.
+//!
+//! ROS launch evaluates the expression as Python code. This module
+//! implements the subset used in launch files: literals, arithmetic,
+//! comparisons, `and`/`or`/`not`, string concatenation, conditional
+//! expressions and the `int()`, `float()`, `str()` and `bool()`
+//! functions. Identifiers refer to launch configurations. Nothing else
+//! is accessible from the expression unless the caller provides more
+//! functions, e.g. the `arg()` and `env()` functions of roslaunch.
+
+use pest::{iterators::Pair, Parser};
+use pest_derive::Parser;
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+};
+
+/// The value of an evaluated expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Value {
+    /// Returns the truth value as Python does. Zero numbers and empty
+    /// strings are false.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(value) => *value,
+            Value::Int(value) => *value != 0,
+            Value::Float(value) => *value != 0.0,
+            Value::Str(value) => !value.is_empty(),
+        }
+    }
+
+    /// Returns the Python type name of the value.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Str(_) => "str",
+        }
+    }
+
+    fn as_number(&self) -> Option<Number> {
+        let number = match *self {
+            Value::Bool(value) => Number::Int(value as i64),
+            Value::Int(value) => Number::Int(value),
+            Value::Float(value) => Number::Float(value),
+            Value::Str(_) => return None,
+        };
+        Some(number)
+    }
+}
+
+/// Formats the value as Python's `str()` does.
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(true) => write!(f, "True"),
+            Value::Bool(false) => write!(f, "False"),
+            Value::Int(value) => write!(f, "{value}"),
+            Value::Float(value) if value.is_nan() => write!(f, "nan"),
+            Value::Float(value) if value.is_infinite() => {
+                write!(f, "{}", if *value > 0.0 { "inf" } else { "-inf" })
+            }
+            Value::Float(value) => write_float(f, *value),
+            Value::Str(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Writes a finite float with the shortest digits that read back the
+/// same. Like Python, it switches to exponent notation if the decimal
+/// exponent is below -4 or at least 16, e.g. `1e-05` and `1e+16`.
+fn write_float(f: &mut fmt::Formatter<'_>, value: f64) -> fmt::Result {
+    let sci = format!("{value:e}");
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+
+    if !(-4..16).contains(&exp) {
+        let sign = if exp < 0 { '-' } else { '+' };
+        return write!(f, "{mantissa}e{sign}{:02}", exp.abs());
+    }
+
+    let text = value.to_string();
+    if text.contains('.') {
+        write!(f, "{text}")
+    } else {
+        write!(f, "{text}.0")
+    }
+}
+
+/// The error returned when an expression cannot be evaluated.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum EvalError {
+    #[error("invalid expression\n{0}")]
+    Syntax(String),
+
+    #[error("name '{0}' is not defined")]
+    UndefinedName(String),
+
+    #[error("unknown function '{0}'")]
+    UnknownFunction(String),
+
+    #[error("{0}")]
+    Type(String),
+
+    #[error("{0}")]
+    Value(String),
+
+    #[error("division by zero")]
+    ZeroDivision,
+
+    #[error("integer overflow")]
+    Overflow,
+}
+
+/// Evaluates the expression. Identifiers are resolved to string values
+/// by `lookup`, which usually returns launch configurations.
+pub fn evaluate<F>(expr: &str, lookup: F) -> Result<Value, EvalError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    evaluate_with(expr, lookup, |_, _| None)
+}
+
+/// Evaluates the expression like `evaluate`, and calls to functions
+/// other than the built-in ones are passed to `call`. It returns `None`
+/// if the function is unknown.
+pub fn evaluate_with<F, G>(expr: &str, lookup: F, call: G) -> Result<Value, EvalError>
+where
+    F: Fn(&str) -> Option<String>,
+    G: Fn(&str, &[Value]) -> Option<Result<Value, EvalError>>,
+{
+    let scope = Scope {
+        lookup: &lookup,
+        call: &call,
+    };
+    parse_expr(expr)?.eval(&scope)
+}
+
+type ExternalCall<'a> = &'a dyn Fn(&str, &[Value]) -> Option<Result<Value, EvalError>>;
+
+/// Resolves names and external functions during evaluation.
+struct Scope<'a> {
+    lookup: &'a dyn Fn(&str) -> Option<String>,
+    call: ExternalCall<'a>,
+}
+
+/// Returns the identifiers that the expression refers to, in order of
+/// appearance and without duplicates.
+pub(crate) fn identifiers(expr: &str) -> Result<Vec<String>, EvalError> {
+    let mut names = vec![];
+    parse_expr(expr)?.visit(&mut |expr| {
+        if let Expr::Ident(name) = expr {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    });
+    Ok(names)
+}
+
+/// Returns the calls to functions other than the built-in ones whose
+/// arguments are all literals, in order of appearance.
+pub(crate) fn literal_calls(expr: &str) -> Result<Vec<(String, Vec<Value>)>, EvalError> {
+    let mut calls = vec![];
+    parse_expr(expr)?.visit(&mut |expr| {
+        let Expr::Call { name, args } = expr else {
+            return;
+        };
+        if is_builtin(name) {
+            return;
+        }
+        let args: Option<Vec<_>> = args
+            .iter()
+            .map(|arg| match arg {
+                Expr::Literal(value) => Some(value.clone()),
+                _ => None,
+            })
+            .collect();
+        if let Some(args) = args {
+            calls.push((name.clone(), args));
+        }
+    });
+    Ok(calls)
+}
+
+/// The deepest parse tree accepted. Building and evaluating the
+/// expression recurse along the tree, so a deeper one, e.g. `not not
+/// ... 1`, could overflow the stack.
+const MAX_DEPTH: usize = 256;
+
+fn parse_expr(expr: &str) -> Result<Expr, EvalError> {
+    let mut pairs =
+        EvalParser::parse(Rule::expr, expr).map_err(|err| EvalError::Syntax(err.to_string()))?;
+    let ternary = pairs.next().unwrap().into_inner().next().unwrap();
+    check_depth(ternary.clone())?;
+    Ok(build_ternary(ternary))
+}
+
+/// Checks the depth of the parse tree without recursion.
+fn check_depth(pair: Pair<Rule>) -> Result<(), EvalError> {
+    let mut stack = vec![(pair, 1)];
+
+    while let Some((pair, depth)) = stack.pop() {
+        if depth > MAX_DEPTH {
+            let (line, col) = pair.line_col();
+            return Err(EvalError::Syntax(format!(
+                "the expression is nested too deeply at {line}:{col}"
+            )));
+        }
+        stack.extend(pair.into_inner().map(|inner| (inner, depth + 1)));
+    }
+
+    Ok(())
+}
+
+#[derive(Parser)]
+#[grammar = "eval.pest"]
+struct EvalParser;
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Ident(String),
+    Call {
+        name: String,
+        args: Vec<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Compare {
+        first: Box<Expr>,
+        rest: Vec<(CmpOp, Expr)>,
+    },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    IfElse {
+        cond: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum UnaryOp {
+    Neg,
+    Pos,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Mod,
+    Pow,
+}
+
+impl BinaryOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::FloorDiv => "//",
+            BinaryOp::Mod => "%",
+            BinaryOp::Pow => "**",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            CmpOp::Eq => "==",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    fn to_f64(self) -> f64 {
+        match self {
+            Number::Int(value) => value as f64,
+            Number::Float(value) => value,
+        }
+    }
+}
+
+fn build_ternary(pair: Pair<Rule>) -> Expr {
+    debug_assert_eq!(pair.as_rule(), Rule::ternary);
+    let mut inner = pair.into_inner().filter(|pair| !is_keyword(pair));
+    let then = build_disjunction(inner.next().unwrap());
+
+    match (inner.next(), inner.next()) {
+        (Some(cond), Some(otherwise)) => Expr::IfElse {
+            cond: Box::new(build_disjunction(cond)),
+            then: Box::new(then),
+            otherwise: Box::new(build_ternary(otherwise)),
+        },
+        _ => then,
+    }
+}
+
+fn build_disjunction(pair: Pair<Rule>) -> Expr {
+    pair.into_inner()
+        .filter(|pair| !is_keyword(pair))
+        .map(build_conjunction)
+        .reduce(|lhs, rhs| Expr::Or(Box::new(lhs), Box::new(rhs)))
+        .unwrap()
+}
+
+fn build_conjunction(pair: Pair<Rule>) -> Expr {
+    pair.into_inner()
+        .filter(|pair| !is_keyword(pair))
+        .map(build_inversion)
+        .reduce(|lhs, rhs| Expr::And(Box::new(lhs), Box::new(rhs)))
+        .unwrap()
+}
+
+fn build_inversion(pair: Pair<Rule>) -> Expr {
+    let inner = pair.into_inner().find(|pair| !is_keyword(pair)).unwrap();
+
+    match inner.as_rule() {
+        Rule::inversion => Expr::Not(Box::new(build_inversion(inner))),
+        Rule::comparison => build_comparison(inner),
+        _ => unreachable!(),
+    }
+}
+
+fn build_comparison(pair: Pair<Rule>) -> Expr {
+    let mut inner = pair.into_inner();
+    let first = build_sum(inner.next().unwrap());
+    let mut rest = vec![];
+
+    while let (Some(op), Some(operand)) = (inner.next(), inner.next()) {
+        let op = match op.as_str() {
+            "==" => CmpOp::Eq,
+            "!=" => CmpOp::Ne,
+            "<" => CmpOp::Lt,
+            "<=" => CmpOp::Le,
+            ">" => CmpOp::Gt,
+            ">=" => CmpOp::Ge,
+            _ => unreachable!(),
+        };
+        rest.push((op, build_sum(operand)));
+    }
+
+    if rest.is_empty() {
+        first
+    } else {
+        Expr::Compare {
+            first: Box::new(first),
+            rest,
+        }
+    }
+}
+
+fn build_sum(pair: Pair<Rule>) -> Expr {
+    build_binary(pair, build_term)
+}
+
+fn build_term(pair: Pair<Rule>) -> Expr {
+    build_binary(pair, build_factor)
+}
+
+/// Builds a left-associative chain of binary operations.
+fn build_binary(pair: Pair<Rule>, build_operand: fn(Pair<Rule>) -> Expr) -> Expr {
+    let mut inner = pair.into_inner();
+    let mut lhs = build_operand(inner.next().unwrap());
+
+    while let (Some(op), Some(rhs)) = (inner.next(), inner.next()) {
+        let op = match op.as_str() {
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "//" => BinaryOp::FloorDiv,
+            "%" => BinaryOp::Mod,
+            _ => unreachable!(),
+        };
+        lhs = Expr::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(build_operand(rhs)),
+        };
+    }
+
+    lhs
+}
+
+fn build_factor(pair: Pair<Rule>) -> Expr {
+    let mut inner = pair.into_inner();
+    let first = inner.next().unwrap();
+
+    match first.as_rule() {
+        Rule::sign_op => {
+            let op = match first.as_str() {
+                "-" => UnaryOp::Neg,
+                "+" => UnaryOp::Pos,
+                _ => unreachable!(),
+            };
+            Expr::Unary {
+                op,
+                operand: Box::new(build_factor(inner.next().unwrap())),
+            }
+        }
+        Rule::power => build_power(first),
+        _ => unreachable!(),
+    }
+}
+
+fn build_power(pair: Pair<Rule>) -> Expr {
+    let mut inner = pair.into_inner();
+    let base = build_primary(inner.next().unwrap());
+
+    match (inner.next(), inner.next()) {
+        (Some(_), Some(exponent)) => Expr::Binary {
+            op: BinaryOp::Pow,
+            lhs: Box::new(base),
+            rhs: Box::new(build_factor(exponent)),
+        },
+        _ => base,
+    }
+}
+
+fn build_primary(pair: Pair<Rule>) -> Expr {
+    match pair.as_rule() {
+        Rule::call => {
+            let mut inner = pair.into_inner();
+            let name = inner.next().unwrap().as_str().to_string();
+            let args = inner.map(build_ternary).collect();
+            Expr::Call { name, args }
+        }
+        Rule::float => Expr::Literal(Value::Float(pair.as_str().parse().unwrap())),
+        Rule::int => match pair.as_str().parse() {
+            Ok(value) => Expr::Literal(Value::Int(value)),
+            // Python integers are unbounded. Keep the magnitude at least.
+            Err(_) => Expr::Literal(Value::Float(pair.as_str().parse().unwrap())),
+        },
+        Rule::string => {
+            let content = pair.into_inner().next().unwrap();
+            Expr::Literal(Value::Str(unescape(content.as_str())))
+        }
+        Rule::boolean => Expr::Literal(Value::Bool(pair.as_str() == "True")),
+        Rule::ident => Expr::Ident(pair.as_str().to_string()),
+        Rule::ternary => build_ternary(pair),
+        _ => unreachable!(),
+    }
+}
+
+fn is_keyword(pair: &Pair<Rule>) -> bool {
+    matches!(
+        pair.as_rule(),
+        Rule::kw_if | Rule::kw_else | Rule::kw_or | Rule::kw_and | Rule::kw_not
+    )
+}
+
+/// Replaces the common Python escape sequences in a string literal.
+fn unescape(text: &str) -> String {
+    let mut buf = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            buf.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => buf.push('\n'),
+            Some('t') => buf.push('\t'),
+            Some('r') => buf.push('\r'),
+            Some('0') => buf.push('\0'),
+            Some(ch @ ('\\' | '\'' | '"')) => buf.push(ch),
+            Some(ch) => {
+                buf.push('\\');
+                buf.push(ch);
+            }
+            None => buf.push('\\'),
+        }
+    }
+
+    buf
+}
+
+impl Expr {
+    /// Calls `f` on this expression and its subexpressions in
+    /// evaluation order.
+    fn visit<F>(&self, f: &mut F)
+    where
+        F: FnMut(&Expr),
+    {
+        f(self);
+
+        match self {
+            Expr::Literal(_) | Expr::Ident(_) => {}
+            Expr::Call { args, .. } => {
+                for arg in args {
+                    arg.visit(f);
+                }
+            }
+            Expr::Unary { operand, .. } | Expr::Not(operand) => operand.visit(f),
+            Expr::Binary { lhs, rhs, .. } | Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+                lhs.visit(f);
+                rhs.visit(f);
+            }
+            Expr::Compare { first, rest } => {
+                first.visit(f);
+                for (_, operand) in rest {
+                    operand.visit(f);
+                }
+            }
+            Expr::IfElse {
+                cond,
+                then,
+                otherwise,
+            } => {
+                then.visit(f);
+                cond.visit(f);
+                otherwise.visit(f);
+            }
+        }
+    }
+
+    fn eval(&self, scope: &Scope) -> Result<Value, EvalError> {
+        let value = match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Ident(name) => match (scope.lookup)(name) {
+                Some(value) => Value::Str(value),
+                None => return Err(EvalError::UndefinedName(name.clone())),
+            },
+            Expr::Call { name, args } => {
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| arg.eval(scope))
+                    .collect::<Result<_, _>>()?;
+                if is_builtin(name) {
+                    call(name, args)?
+                } else {
+                    match (scope.call)(name, &args) {
+                        Some(result) => result?,
+                        None => return Err(EvalError::UnknownFunction(name.clone())),
+                    }
+                }
+            }
+            Expr::Unary { op, operand } => unary(*op, operand.eval(scope)?)?,
+            Expr::Binary { op, lhs, rhs } => binary(*op, lhs.eval(scope)?, rhs.eval(scope)?)?,
+            Expr::Compare { first, rest } => {
+                // Chained comparisons like `a < b < c` are evaluated
+                // pairwise and short-circuit.
+                let mut lhs = first.eval(scope)?;
+
+                for (op, rhs) in rest {
+                    let rhs = rhs.eval(scope)?;
+                    if !compare(*op, &lhs, &rhs)? {
+                        return Ok(Value::Bool(false));
+                    }
+                    lhs = rhs;
+                }
+
+                Value::Bool(true)
+            }
+            Expr::Not(operand) => Value::Bool(!operand.eval(scope)?.is_truthy()),
+            Expr::And(lhs, rhs) => {
+                let lhs = lhs.eval(scope)?;
+                if lhs.is_truthy() {
+                    rhs.eval(scope)?
+                } else {
+                    lhs
+                }
+            }
+            Expr::Or(lhs, rhs) => {
+                let lhs = lhs.eval(scope)?;
+                if lhs.is_truthy() {
+                    lhs
+                } else {
+                    rhs.eval(scope)?
+                }
+            }
+            Expr::IfElse {
+                cond,
+                then,
+                otherwise,
+            } => {
+                if cond.eval(scope)?.is_truthy() {
+                    then.eval(scope)?
+                } else {
+                    otherwise.eval(scope)?
+                }
+            }
+        };
+
+        Ok(value)
+    }
+}
+
+fn is_builtin(name: &str) -> bool {
+    matches!(name, "int" | "float" | "str" | "bool")
+}
+
+fn call(name: &str, args: Vec<Value>) -> Result<Value, EvalError> {
+    let convert: fn(Value) -> Result<Value, EvalError> = match name {
+        "int" => to_int,
+        "float" => to_float,
+        "str" => |value| Ok(Value::Str(value.to_string())),
+        "bool" => |value| Ok(Value::Bool(value.is_truthy())),
+        _ => return Err(EvalError::UnknownFunction(name.to_string())),
+    };
+
+    let Ok([arg]) = <[Value; 1]>::try_from(args) else {
+        return Err(EvalError::Type(format!(
+            "{name}() takes exactly one argument"
+        )));
+    };
+    convert(arg)
+}
+
+fn to_int(value: Value) -> Result<Value, EvalError> {
+    let value = match value {
+        Value::Bool(value) => value as i64,
+        Value::Int(value) => value,
+        Value::Float(value) if value.is_finite() => value.trunc() as i64,
+        Value::Float(value) => {
+            return Err(EvalError::Value(format!(
+                "cannot convert float {} to integer",
+                Value::Float(value)
+            )))
+        }
+        Value::Str(text) => text.trim().parse().map_err(|_| {
+            EvalError::Value(format!("invalid literal for int() with base 10: '{text}'"))
+        })?,
+    };
+    Ok(Value::Int(value))
+}
+
+fn to_float(value: Value) -> Result<Value, EvalError> {
+    let value = match value {
+        Value::Bool(value) => value as i64 as f64,
+        Value::Int(value) => value as f64,
+        Value::Float(value) => value,
+        Value::Str(text) => text.trim().parse().map_err(|_| {
+            EvalError::Value(format!("could not convert string to float: '{text}'"))
+        })?,
+    };
+    Ok(Value::Float(value))
+}
+
+fn unary(op: UnaryOp, operand: Value) -> Result<Value, EvalError> {
+    let value = match (op, operand.as_number()) {
+        (UnaryOp::Neg, Some(Number::Int(value))) => {
+            Value::Int(value.checked_neg().ok_or(EvalError::Overflow)?)
+        }
+        (UnaryOp::Neg, Some(Number::Float(value))) => Value::Float(-value),
+        (UnaryOp::Pos, Some(Number::Int(value))) => Value::Int(value),
+        (UnaryOp::Pos, Some(Number::Float(value))) => Value::Float(value),
+        (_, None) => {
+            let symbol = match op {
+                UnaryOp::Neg => "-",
+                UnaryOp::Pos => "+",
+            };
+            return Err(EvalError::Type(format!(
+                "bad operand type for unary {symbol}: '{}'",
+                operand.type_name()
+            )));
+        }
+    };
+    Ok(value)
+}
+
+/// The longest string an expression may build, so that an untrusted
+/// expression like `'x' * 10**12` cannot exhaust memory.
+const MAX_STR_LEN: usize = 1 << 20;
+
+fn check_str_len(len: Option<usize>) -> Result<(), EvalError> {
+    match len {
+        Some(len) if len <= MAX_STR_LEN => Ok(()),
+        _ => Err(EvalError::Value(format!(
+            "the resulting string is longer than {MAX_STR_LEN} bytes"
+        ))),
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value, EvalError> {
+    match (op, &lhs, &rhs) {
+        (BinaryOp::Add, Value::Str(lhs), Value::Str(rhs)) => {
+            check_str_len(lhs.len().checked_add(rhs.len()))?;
+            return Ok(Value::Str(format!("{lhs}{rhs}")));
+        }
+        (BinaryOp::Mul, Value::Str(text), other) | (BinaryOp::Mul, other, Value::Str(text)) => {
+            if let Some(Number::Int(count)) = other.as_number() {
+                let count = count.max(0) as usize;
+                check_str_len(text.len().checked_mul(count))?;
+                return Ok(Value::Str(text.repeat(count)));
+            }
+        }
+        _ => {}
+    }
+
+    let (Some(lhs_num), Some(rhs_num)) = (lhs.as_number(), rhs.as_number()) else {
+        return Err(EvalError::Type(format!(
+            "unsupported operand type(s) for {}: '{}' and '{}'",
+            op.symbol(),
+            lhs.type_name(),
+            rhs.type_name()
+        )));
+    };
+
+    match (lhs_num, rhs_num) {
+        (Number::Int(lhs), Number::Int(rhs)) => int_binary(op, lhs, rhs),
+        (lhs, rhs) => float_binary(op, lhs.to_f64(), rhs.to_f64()),
+    }
+}
+
+fn int_binary(op: BinaryOp, lhs: i64, rhs: i64) -> Result<Value, EvalError> {
+    let value = match op {
+        BinaryOp::Add => lhs.checked_add(rhs),
+        BinaryOp::Sub => lhs.checked_sub(rhs),
+        BinaryOp::Mul => lhs.checked_mul(rhs),
+        BinaryOp::Div => return float_binary(op, lhs as f64, rhs as f64),
+        BinaryOp::FloorDiv | BinaryOp::Mod if rhs == 0 => return Err(EvalError::ZeroDivision),
+        BinaryOp::FloorDiv => {
+            let (quot, rem) = (lhs.checked_div(rhs), lhs.checked_rem(rhs).unwrap_or(0));
+            quot.map(|quot| {
+                if rem != 0 && (rem < 0) != (rhs < 0) {
+                    quot - 1
+                } else {
+                    quot
+                }
+            })
+        }
+        BinaryOp::Mod => {
+            let rem = lhs.checked_rem(rhs).unwrap_or(0);
+            Some(if rem != 0 && (rem < 0) != (rhs < 0) {
+                rem + rhs
+            } else {
+                rem
+            })
+        }
+        BinaryOp::Pow => match u32::try_from(rhs) {
+            Ok(exp) => lhs.checked_pow(exp),
+            Err(_) if rhs < 0 => return float_binary(op, lhs as f64, rhs as f64),
+            Err(_) => None,
+        },
+    };
+
+    value.map(Value::Int).ok_or(EvalError::Overflow)
+}
+
+fn float_binary(op: BinaryOp, lhs: f64, rhs: f64) -> Result<Value, EvalError> {
+    let value = match op {
+        BinaryOp::Add => lhs + rhs,
+        BinaryOp::Sub => lhs - rhs,
+        BinaryOp::Mul => lhs * rhs,
+        BinaryOp::Div | BinaryOp::FloorDiv | BinaryOp::Mod if rhs == 0.0 => {
+            return Err(EvalError::ZeroDivision)
+        }
+        BinaryOp::Div => lhs / rhs,
+        BinaryOp::FloorDiv => (lhs / rhs).floor(),
+        BinaryOp::Mod => {
+            let rem = lhs % rhs;
+            if rem != 0.0 && (rem < 0.0) != (rhs < 0.0) {
+                rem + rhs
+            } else {
+                rem
+            }
+        }
+        BinaryOp::Pow if lhs == 0.0 && rhs < 0.0 => return Err(EvalError::ZeroDivision),
+        BinaryOp::Pow => lhs.powf(rhs),
+    };
+    Ok(Value::Float(value))
+}
+
+fn compare(op: CmpOp, lhs: &Value, rhs: &Value) -> Result<bool, EvalError> {
+    let ordering = match (lhs, rhs) {
+        (Value::Str(lhs), Value::Str(rhs)) => Some(lhs.cmp(rhs)),
+        _ => match (lhs.as_number(), rhs.as_number()) {
+            (Some(Number::Int(lhs)), Some(Number::Int(rhs))) => Some(lhs.cmp(&rhs)),
+            (Some(lhs), Some(rhs)) => lhs.to_f64().partial_cmp(&rhs.to_f64()),
+            // Values of different types are never equal and cannot be
+            // ordered.
+            _ => match op {
+                CmpOp::Eq => return Ok(false),
+                CmpOp::Ne => return Ok(true),
+                _ => {
+                    return Err(EvalError::Type(format!(
+                        "'{}' not supported between instances of '{}' and '{}'",
+                        op.symbol(),
+                        lhs.type_name(),
+                        rhs.type_name()
+                    )))
+                }
+            },
+        },
+    };
+
+    // A NaN operand makes every comparison false except `!=`.
+    let Some(ordering) = ordering else {
+        return Ok(matches!(op, CmpOp::Ne));
+    };
+
+    let result = match op {
+        CmpOp::Eq => ordering == Ordering::Equal,
+        CmpOp::Ne => ordering != Ordering::Equal,
+        CmpOp::Lt => ordering == Ordering::Less,
+        CmpOp::Le => ordering != Ordering::Greater,
+        CmpOp::Gt => ordering == Ordering::Greater,
+        CmpOp::Ge => ordering != Ordering::Less,
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expr: &str) -> Result<Value, EvalError> {
+        evaluate(expr, |_| None)
+    }
+
+    #[test]
+    fn evaluate_expressions() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(Value::Int(7)));
+        assert_eq!(eval("'a' + 'b'"), Ok(Value::Str("ab".to_string())));
+        assert_eq!(eval("'ab' * 3"), Ok(Value::Str("ababab".to_string())));
+        assert_eq!(eval("2 * 'x'"), Ok(Value::Str("xx".to_string())));
+        assert_eq!(eval("'x' * -1"), Ok(Value::Str(String::new())));
+    }
+
+    #[test]
+    fn display_floats() {
+        for (value, text) in [
+            (1.0, "1.0"),
+            (-0.0, "-0.0"),
+            (0.5, "0.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1e15, "1000000000000000.0"),
+            (1e16, "1e+16"),
+            (1.5e300, "1.5e+300"),
+            (0.0001, "0.0001"),
+            (1e-05, "1e-05"),
+            (-2.5e-7, "-2.5e-07"),
+            (f64::INFINITY, "inf"),
+            (f64::NAN, "nan"),
+        ] {
+            assert_eq!(Value::Float(value).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        for expr in [
+            format!("{}1", "not ".repeat(5000)),
+            format!("{}1", "-".repeat(5000)),
+            format!("{}1{}", "(".repeat(100), ")".repeat(100)),
+            format!("1{}", "**1".repeat(5000)),
+            format!("1{}", " if 1 else 1".repeat(5000)),
+        ] {
+            assert!(matches!(eval(&expr), Err(EvalError::Syntax(_))));
+        }
+
+        assert_eq!(
+            eval(&format!("{}1", "not ".repeat(100))),
+            Ok(Value::Bool(true))
+        );
+        assert_eq!(
+            eval(&format!("{}1{}", "(".repeat(20), ")".repeat(20))),
+            Ok(Value::Int(1))
+        );
+    }
+
+    #[test]
+    fn repeat_long_string() {
+        assert!(matches!(
+            eval("'x' * 1000000000000"),
+            Err(EvalError::Value(_))
+        ));
+        assert!(matches!(
+            eval("'xy' * 9223372036854775807"),
+            Err(EvalError::Value(_))
+        ));
+        assert_eq!(
+            eval(&format!("'x' * {MAX_STR_LEN}")).map(|value| value.to_string().len()),
+            Ok(MAX_STR_LEN)
+        );
+    }
+}
@@ -1,5 +1,28 @@
+mod anon;
+mod command;
+mod condition;
+mod context;
+mod deps;
+mod error;
+mod eval;
+mod package;
 mod parse;
+mod partial;
+mod registry;
+mod render;
+mod ros1;
 mod types;
 
-pub use parse::parse;
-pub use types::{SubstBlock, Substitution};
+pub use anon::AnonNames;
+pub use command::{split_command, CommandOutput, CommandPolicy, CommandRunner};
+pub use condition::{parse_bool, values_equal};
+pub use context::{ProcessContext, SubstitutionContext};
+pub use deps::{dependencies, Dependency};
+pub use error::SubstError;
+pub use eval::{evaluate, EvalError, Value};
+pub use package::{find_in_path, AmentIndexLocator, MemoryLocator, PackageLocator};
+pub use parse::{parse, parse_with_dialect, Dialect, Rule};
+pub use partial::partial_eval;
+pub use registry::SubstitutionRegistry;
+pub use render::to_source;
+pub use types::{eval_blocks, Span, SubstArg, SubstBlock, Substitution};
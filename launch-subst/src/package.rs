@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+/// Locates the installation directories of ROS packages.
+///
+/// It is used to evaluate `$(find)`, `$(find-pkg-share)` and
+/// `$(find-pkg-prefix)` substitutions.
+pub trait PackageLocator {
+    /// Returns the install prefix of the package, or `None` if the
+    /// package cannot be found.
+    fn find_prefix(&self, pkg: &str) -> Option<PathBuf>;
+
+    /// Returns the share directory of the package, or `None` if the
+    /// package cannot be found.
+    ///
+    /// The default implementation returns `<prefix>/share/<pkg>`.
+    fn find_share(&self, pkg: &str) -> Option<PathBuf> {
+        let prefix = self.find_prefix(pkg)?;
+        Some(prefix.join("share").join(pkg))
+    }
+
+    /// Returns the path to an executable of the package, or `None` if
+    /// either cannot be found. It is used to evaluate `$(exec-in-pkg)`.
+    ///
+    /// The default implementation looks for `<prefix>/lib/<pkg>/<exec>`.
+    fn find_executable(&self, pkg: &str, exec: &str) -> Option<PathBuf> {
+        let prefix = self.find_prefix(pkg)?;
+        let path = prefix.join("lib").join(pkg).join(exec);
+        is_executable(&path).then_some(path)
+    }
+}
+
+impl<T> PackageLocator for &T
+where
+    T: PackageLocator + ?Sized,
+{
+    fn find_prefix(&self, pkg: &str) -> Option<PathBuf> {
+        (**self).find_prefix(pkg)
+    }
+
+    fn find_share(&self, pkg: &str) -> Option<PathBuf> {
+        (**self).find_share(pkg)
+    }
+
+    fn find_executable(&self, pkg: &str, exec: &str) -> Option<PathBuf> {
+        (**self).find_executable(pkg, exec)
+    }
+}
+
+/// Searches the directories in a `PATH`-like list for the executable,
+/// like the `which` command. It is used to evaluate `$(find-exec)`. A
+/// name containing a path separator is checked as a path directly.
+pub fn find_in_path(name: &str, path: &OsStr) -> Option<PathBuf> {
+    if name.contains(std::path::MAIN_SEPARATOR) {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+
+    env::split_paths(path)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        path.metadata()
+            .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Locates packages using the ament resource index.
+///
+/// A package is found under a prefix if the marker file
+/// `<prefix>/share/ament_index/resource_index/packages/<pkg>` exists.
+/// Prefixes are searched in order and the first match wins.
+#[derive(Debug, Clone, Default)]
+pub struct AmentIndexLocator {
+    pub prefixes: Vec<PathBuf>,
+}
+
+impl AmentIndexLocator {
+    pub fn new<I>(prefixes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+    {
+        Self {
+            prefixes: prefixes.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Creates a locator that searches the prefixes listed in the
+    /// `AMENT_PREFIX_PATH` environment variable.
+    pub fn from_env() -> Self {
+        let prefixes = match env::var_os("AMENT_PREFIX_PATH") {
+            Some(paths) => env::split_paths(&paths)
+                .filter(|path| !path.as_os_str().is_empty())
+                .collect(),
+            None => vec![],
+        };
+        Self { prefixes }
+    }
+}
+
+impl PackageLocator for AmentIndexLocator {
+    fn find_prefix(&self, pkg: &str) -> Option<PathBuf> {
+        self.prefixes
+            .iter()
+            .find(|prefix| {
+                prefix
+                    .join("share/ament_index/resource_index/packages")
+                    .join(pkg)
+                    .is_file()
+            })
+            .cloned()
+    }
+}
+
+/// An in-memory package table mapping package names to prefixes.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLocator {
+    pub prefixes: HashMap<String, PathBuf>,
+}
+
+impl MemoryLocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<P>(&mut self, pkg: impl Into<String>, prefix: P) -> Option<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        self.prefixes
+            .insert(pkg.into(), prefix.as_ref().to_path_buf())
+    }
+}
+
+impl<S, P> FromIterator<(S, P)> for MemoryLocator
+where
+    S: Into<String>,
+    P: Into<PathBuf>,
+{
+    fn from_iter<T: IntoIterator<Item = (S, P)>>(iter: T) -> Self {
+        Self {
+            prefixes: iter
+                .into_iter()
+                .map(|(pkg, prefix)| (pkg.into(), prefix.into()))
+                .collect(),
+        }
+    }
+}
+
+impl PackageLocator for MemoryLocator {
+    fn find_prefix(&self, pkg: &str) -> Option<PathBuf> {
+        self.prefixes.get(pkg).cloned()
+    }
+}
@@ -1,10 +1,14 @@
-use crate::types::{SubstBlock, Substitution};
+use crate::{
+    ros1,
+    types::{Span, SubstArg, SubstBlock, Substitution},
+};
 use pest::{
     error::{Error, ErrorVariant},
     iterators::Pair,
     Parser,
 };
 use pest_derive::Parser;
+use std::borrow::Cow;
 
 macro_rules! bail {
     ($span:expr, $($tt:tt)*) => {
@@ -17,16 +21,72 @@ macro_rules! bail {
     };
 }
 
-pub fn parse(input: &str) -> Result<Vec<SubstBlock>, Error<Rule>> {
-    let mut pairs = ExprParser::parse(Rule::expr, input).unwrap();
+/// The generation of the substitution syntax.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Dialect {
+    /// The syntax of ROS 2 launch files.
+    #[default]
+    Ros2,
+
+    /// The syntax of ROS 1 roslaunch files.
+    Ros1,
+}
+
+/// Parses the text in the ROS 2 syntax. The blocks borrow from the
+/// text unless escape sequences have to be replaced.
+pub fn parse(input: &str) -> Result<Vec<SubstBlock<'_>>, Error<Rule>> {
+    let mut pairs = ExprParser::parse(Rule::expr, input)?;
     parse_expr(pairs.next().unwrap())
 }
 
+/// Parses the text in the syntax of the dialect. Both dialects produce
+/// the same substitution types.
+pub fn parse_with_dialect(
+    input: &str,
+    dialect: Dialect,
+) -> Result<Vec<SubstBlock<'_>>, Error<Rule>> {
+    match dialect {
+        Dialect::Ros2 => parse(input),
+        Dialect::Ros1 => ros1::parse(input),
+    }
+}
+
 #[derive(Parser)]
 #[grammar = "grammar.pest"] // relative to src
 struct ExprParser;
 
-fn parse_expr(pair: Pair<Rule>) -> Result<Vec<SubstBlock>, Error<Rule>> {
+/// The commands of the substitutions built into ROS launch. Other
+/// commands are parsed as [`Substitution::Other`].
+pub(crate) const BUILTIN_COMMANDS: &[&str] = &[
+    "env",
+    "optenv",
+    "find",
+    "find-pkg-share",
+    "find-pkg-prefix",
+    "anon",
+    "arg",
+    "var",
+    "launch_configuration",
+    "find-exec",
+    "exec-in-pkg",
+    "command",
+    "param",
+    "eval",
+    "not",
+    "and",
+    "or",
+    "equals",
+    "not-equals",
+    "dirname",
+    "filename",
+];
+
+/// The deepest nesting of substitutions accepted. Converting and
+/// evaluating substitutions recurse into their arguments, so deeper
+/// nesting could overflow the stack.
+const MAX_NESTING: usize = 32;
+
+fn parse_expr(pair: Pair<'_, Rule>) -> Result<Vec<SubstBlock<'_>>, Error<Rule>> {
     debug_assert_eq!(pair.as_rule(), Rule::expr);
     pair.into_inner()
         .filter(|pair| pair.as_rule() == Rule::block)
@@ -34,40 +94,58 @@ fn parse_expr(pair: Pair<Rule>) -> Result<Vec<SubstBlock>, Error<Rule>> {
         .collect()
 }
 
-fn parse_block(pair: Pair<Rule>) -> Result<SubstBlock, Error<Rule>> {
+fn parse_block(pair: Pair<'_, Rule>) -> Result<SubstBlock<'_>, Error<Rule>> {
     debug_assert_eq!(pair.as_rule(), Rule::block);
     let inner = pair.into_inner().next().unwrap();
+    let span = span_of(&inner);
     let block = match inner.as_rule() {
-        Rule::subst => SubstBlock::Substitution(parse_subst(inner)?),
-        Rule::text => SubstBlock::Text(parse_text(inner)),
+        Rule::subst => SubstBlock::Substitution {
+            subst: parse_subst(inner, 1)?,
+            span,
+        },
+        Rule::text => SubstBlock::text(parse_text(inner), span),
         _ => unreachable!(),
     };
     Ok(block)
 }
 
-fn parse_text(pair: Pair<Rule>) -> String {
-    pair.as_str().to_string()
+fn parse_text(pair: Pair<'_, Rule>) -> Cow<'_, str> {
+    let text = pair.as_str();
+    if text.contains("\\$") {
+        Cow::Owned(text.replace("\\$", "$"))
+    } else {
+        Cow::Borrowed(text)
+    }
 }
 
-fn parse_subst(pair: Pair<Rule>) -> Result<Substitution, Error<Rule>> {
+/// Parses the substitution at the nesting depth, which is 1 for a
+/// substitution outside any other one.
+fn parse_subst(pair: Pair<'_, Rule>, depth: usize) -> Result<Substitution<'_>, Error<Rule>> {
     let span = pair.as_span();
+    if depth > MAX_NESTING {
+        bail!(
+            span,
+            "substitutions cannot be nested deeper than {MAX_NESTING} levels"
+        );
+    }
+
     let mut inner = pair.into_inner();
     let command = parse_command(inner.next().unwrap());
-    let args: Vec<_> = inner.map(|pair| parse_arg(pair)).collect();
+    let args: Vec<_> = inner
+        .map(|pair| parse_arg(pair, depth))
+        .collect::<Result<_, _>>()?;
 
-    let subst = match command.as_str() {
+    let subst = match command {
         "env" => {
-            let [var] = args.as_slice() else {
+            let Ok([var]) = <[_; 1]>::try_from(args) else {
                 bail!(span, "expect one argument: ENVIRONMENT_VARIABLE");
             };
-            Substitution::Env {
-                variable: var.to_string(),
-            }
+            Substitution::Env { variable: var }
         }
         "optenv" => {
-            let (var, default) = match args.as_slice() {
-                [var] => (var, None),
-                [var, default] => (var, Some(default.to_string())),
+            let mut args = args.into_iter();
+            let (var, default) = match (args.next(), args.next(), args.next()) {
+                (Some(var), default, None) => (var, default),
                 _ => bail!(
                     span,
                     "expect arguments: ENVIRONMENT_VARIABLE [DEFAULT_VALUE]"
@@ -75,51 +153,264 @@ fn parse_subst(pair: Pair<Rule>) -> Result<Substitution, Error<Rule>> {
             };
 
             Substitution::OptEnv {
-                variable: var.to_string(),
+                variable: var,
                 default_value: default,
             }
         }
         "find" => {
-            let [pkg] = args.as_slice() else {
+            let Ok([pkg]) = <[_; 1]>::try_from(args) else {
                 bail!(span, "expect one argument: PACKAGE_NAME");
             };
 
-            Substitution::Find {
-                pkg: pkg.to_string(),
-            }
+            Substitution::Find { pkg }
+        }
+        "find-pkg-share" => {
+            let Ok([pkg]) = <[_; 1]>::try_from(args) else {
+                bail!(span, "expect one argument: PACKAGE_NAME");
+            };
+
+            Substitution::FindPkgShare { pkg }
+        }
+        "find-pkg-prefix" => {
+            let Ok([pkg]) = <[_; 1]>::try_from(args) else {
+                bail!(span, "expect one argument: PACKAGE_NAME");
+            };
+
+            Substitution::FindPkgPrefix { pkg }
         }
         "anon" => {
-            let [name] = args.as_slice() else {
+            let Ok([name]) = <[_; 1]>::try_from(args) else {
                 bail!(span, "expect one argument: NAME");
             };
 
-            Substitution::Anon {
-                name: name.to_string(),
-            }
+            Substitution::Anon { name }
         }
         "arg" => {
-            let [name] = args.as_slice() else {
+            let Ok([name]) = <[_; 1]>::try_from(args) else {
                 bail!(span, "expect one argument: ARG_NAME");
             };
 
-            Substitution::Arg {
-                name: name.to_string(),
+            Substitution::Arg { name }
+        }
+        "var" => {
+            let Ok([name]) = <[_; 1]>::try_from(args) else {
+                bail!(span, "expect one argument: NAME");
+            };
+
+            Substitution::Var { name }
+        }
+        "launch_configuration" => {
+            let Ok([name]) = <[_; 1]>::try_from(args) else {
+                bail!(span, "expect one argument: NAME");
+            };
+
+            Substitution::LaunchConfiguration { name }
+        }
+        "find-exec" => {
+            let Ok([name]) = <[_; 1]>::try_from(args) else {
+                bail!(span, "expect one argument: EXECUTABLE_NAME");
+            };
+
+            Substitution::FindExec { name }
+        }
+        "exec-in-pkg" => {
+            let Ok([exec, pkg]) = <[_; 2]>::try_from(args) else {
+                bail!(span, "expect two arguments: EXECUTABLE_NAME PACKAGE_NAME");
+            };
+
+            Substitution::ExecInPkg { exec, pkg }
+        }
+        "command" => {
+            let mut args = args.into_iter();
+            let (command, on_stderr) = match (args.next(), args.next(), args.next()) {
+                (Some(command), on_stderr, None) => (command, on_stderr),
+                _ => bail!(span, "expect arguments: COMMAND [ON_STDERR]"),
+            };
+
+            // The mode can only be checked here if it is literal text.
+            if let Some([SubstBlock::Text { text: mode, .. }]) = on_stderr.as_deref() {
+                if !["fail", "ignore", "warn", "capture"].contains(&mode.as_ref()) {
+                    bail!(
+                        span,
+                        "expect ON_STDERR to be 'fail', 'ignore', 'warn' or 'capture', but get '{mode}'"
+                    );
+                }
             }
+
+            Substitution::Command { command, on_stderr }
+        }
+        "param" => {
+            let Ok([name]) = <[_; 1]>::try_from(args) else {
+                bail!(span, "expect one argument: PARAMETER_NAME");
+            };
+
+            Substitution::Param { name }
         }
         "eval" => {
-            todo!("eval command is not implemented");
+            let Ok([expr]) = <[_; 1]>::try_from(args) else {
+                bail!(span, "expect one argument: EXPRESSION");
+            };
+
+            Substitution::Eval {
+                expr,
+                dialect: Dialect::Ros2,
+            }
+        }
+        "not" => {
+            let Ok([value]) = <[_; 1]>::try_from(args) else {
+                bail!(span, "expect one argument: CONDITION");
+            };
+
+            Substitution::Not { value }
         }
-        "dirname" => Substitution::DirName,
-        _ => Substitution::Other { args },
+        "and" | "or" => {
+            let Ok([lhs, rhs]) = <[_; 2]>::try_from(args) else {
+                bail!(span, "expect two arguments: LEFT_CONDITION RIGHT_CONDITION");
+            };
+
+            if command == "and" {
+                Substitution::And { lhs, rhs }
+            } else {
+                Substitution::Or { lhs, rhs }
+            }
+        }
+        "equals" | "not-equals" => {
+            let Ok([lhs, rhs]) = <[_; 2]>::try_from(args) else {
+                bail!(span, "expect two arguments: LEFT_VALUE RIGHT_VALUE");
+            };
+
+            if command == "equals" {
+                Substitution::Equals { lhs, rhs }
+            } else {
+                Substitution::NotEquals { lhs, rhs }
+            }
+        }
+        "dirname" => {
+            if !args.is_empty() {
+                bail!(span, "expect no arguments");
+            }
+            Substitution::DirName
+        }
+        "filename" => {
+            if !args.is_empty() {
+                bail!(span, "expect no arguments");
+            }
+            Substitution::FileName
+        }
+        _ => Substitution::Other {
+            command: Cow::Borrowed(command),
+            args,
+        },
     };
 
     Ok(subst)
 }
 
-fn parse_command(pair: Pair<Rule>) -> String {
-    pair.as_str().to_string()
+fn parse_command(pair: Pair<'_, Rule>) -> &str {
+    pair.as_str()
 }
 
-fn parse_arg(pair: Pair<Rule>) -> String {
-    pair.as_str().to_string()
+/// Parses an argument of a substitution at the nesting depth.
+fn parse_arg(pair: Pair<'_, Rule>, depth: usize) -> Result<SubstArg<'_>, Error<Rule>> {
+    debug_assert_eq!(pair.as_rule(), Rule::arg);
+    let span = span_of(&pair);
+    let inner = pair.into_inner().next().unwrap();
+
+    let blocks = inner
+        .into_inner()
+        .map(|pair| {
+            let span = span_of(&pair);
+            let block = match pair.as_rule() {
+                Rule::subst => SubstBlock::Substitution {
+                    subst: parse_subst(pair, depth + 1)?,
+                    span,
+                },
+                Rule::unquoted_text => SubstBlock::text(pair.as_str(), span),
+                Rule::single_quoted_text | Rule::double_quoted_text => {
+                    SubstBlock::text(unescape(pair.as_str()), span)
+                }
+                _ => unreachable!(),
+            };
+            Ok(block)
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(SubstArg::new(blocks, span))
+}
+
+fn span_of(pair: &Pair<'_, Rule>) -> Span {
+    let span = pair.as_span();
+    span.start()..span.end()
+}
+
+/// Replaces escape sequences `\$`, `\'`, `\"` and `\\` in quoted
+/// arguments with the escaped characters.
+fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut buf = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('\\', Some(&next @ ('$' | '\'' | '"' | '\\'))) => {
+                buf.push(next);
+                chars.next();
+            }
+            _ => buf.push(ch),
+        }
+    }
+
+    Cow::Owned(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_commands() {
+        for command in BUILTIN_COMMANDS {
+            for input in [format!("$({command})"), format!("$({command} a)")] {
+                assert!(
+                    !matches!(
+                        parse(&input).as_deref(),
+                        Ok([SubstBlock::Substitution {
+                            subst: Substitution::Other { .. },
+                            ..
+                        }])
+                    ),
+                    "{input}"
+                );
+            }
+        }
+        assert!(matches!(
+            parse("$(robot-serial)").as_deref(),
+            Ok([SubstBlock::Substitution {
+                subst: Substitution::Other { .. },
+                ..
+            }])
+        ));
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let nested = |depth| format!("{}x{}", "$(env ".repeat(depth), ")".repeat(depth));
+
+        assert!(parse(&nested(MAX_NESTING)).is_ok());
+
+        assert!(parse(&nested(1000)).is_err());
+
+        let err = parse(&nested(MAX_NESTING + 1)).unwrap_err();
+        let ErrorVariant::CustomError { message } = &err.variant else {
+            panic!("expect a custom error");
+        };
+        assert!(message.contains("nested"));
+        assert_eq!(
+            err.location,
+            pest::error::InputLocation::Span((6 * MAX_NESTING, 6 * MAX_NESTING + 8))
+        );
+    }
 }
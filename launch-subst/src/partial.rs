@@ -0,0 +1,112 @@
+//! Partial evaluation of substitutions.
+
+use crate::{
+    context::SubstitutionContext,
+    types::{Span, SubstArg, SubstBlock, Substitution},
+};
+
+/// Evaluates the substitutions that can be evaluated and keeps the
+/// others as they are.
+///
+/// Arguments are reduced first. A substitution whose arguments are all
+/// known is replaced by its value unless the evaluation fails, e.g.
+/// because a launch configuration is not defined. Otherwise, it is kept
+/// with the reduced arguments. For example,
+/// `$(find-pkg-share nav)/$(arg map)` gives `/opt/ros/share/nav/$(arg
+/// map)` if `map` is not defined.
+///
+/// `$(command)` is always kept, so that previews never run programs.
+///
+/// Adjacent text is merged, so the result is what the parser would
+/// produce from the rendered text.
+pub fn partial_eval<'a, C>(blocks: &[SubstBlock<'a>], ctx: &C) -> Vec<SubstBlock<'a>>
+where
+    C: SubstitutionContext + ?Sized,
+{
+    let mut output: Vec<SubstBlock> = vec![];
+
+    for block in blocks {
+        let reduced = match block {
+            SubstBlock::Text { .. } => block.clone(),
+            SubstBlock::Substitution { subst, span } => partial_eval_subst(subst, span, ctx),
+        };
+
+        match (output.last_mut(), reduced) {
+            (_, SubstBlock::Text { text, .. }) if text.is_empty() => {}
+            (
+                Some(SubstBlock::Text {
+                    text: prev,
+                    span: prev_span,
+                }),
+                SubstBlock::Text { text, span },
+            ) => {
+                prev.to_mut().push_str(&text);
+                prev_span.end = span.end;
+            }
+            (_, reduced) => output.push(reduced),
+        }
+    }
+
+    output
+}
+
+/// Reduces the substitution. Its value takes over the span of the
+/// substitution.
+fn partial_eval_subst<'a, C>(subst: &Substitution<'a>, span: &Span, ctx: &C) -> SubstBlock<'a>
+where
+    C: SubstitutionContext + ?Sized,
+{
+    let reduced = subst.map_args(|arg| SubstArg::new(partial_eval(arg, ctx), arg.span.clone()));
+
+    let is_known = reduced.args().iter().all(|arg| {
+        arg.iter()
+            .all(|block| matches!(block, SubstBlock::Text { .. }))
+    });
+
+    if is_known && !matches!(reduced, Substitution::Command { .. }) {
+        if let Ok(value) = reduced.eval(ctx) {
+            return SubstBlock::text(value, span.clone());
+        }
+    }
+
+    SubstBlock::Substitution {
+        subst: reduced,
+        span: span.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse::parse, render::to_source, CommandPolicy, ProcessContext};
+
+    fn preview(text: &str, ctx: &ProcessContext) -> String {
+        to_source(&partial_eval(&parse(text).unwrap(), ctx))
+    }
+
+    #[test]
+    fn keep_unknown_substitutions() {
+        let mut ctx = ProcessContext::new();
+        ctx.launch_configurations
+            .insert("robot".to_string(), "r1".to_string());
+
+        assert_eq!(preview("$(var robot)/$(var map)", &ctx), "r1/$(var map)");
+        assert_eq!(
+            preview("$(env $(var robot)_HOME_UNDEFINED_FOR_TEST)", &ctx),
+            "$(env r1_HOME_UNDEFINED_FOR_TEST)"
+        );
+    }
+
+    #[test]
+    fn keep_commands() {
+        let mut ctx = ProcessContext::new();
+        ctx.command_runner.policy = CommandPolicy::Allow;
+        ctx.launch_configurations
+            .insert("word".to_string(), "hello".to_string());
+
+        assert_eq!(
+            preview("$(command 'echo $(var word)')", &ctx),
+            "$(command 'echo hello')"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{error::SubstError, parse::BUILTIN_COMMANDS};
+use std::{collections::HashMap, fmt, ops::RangeInclusive};
+
+type Handler = Box<dyn Fn(&[String]) -> Result<String, String>>;
+
+/// Handlers of user-defined substitutions.
+///
+/// A substitution not built into ROS launch, e.g. `$(robot-serial)`,
+/// is parsed as [`Substitution::Other`](crate::Substitution::Other)
+/// and evaluated by the handler registered with its command name.
+/// Built-in substitutions cannot be overridden.
+#[derive(Default)]
+pub struct SubstitutionRegistry {
+    entries: HashMap<String, Entry>,
+}
+
+struct Entry {
+    num_args: RangeInclusive<usize>,
+    handler: Handler,
+}
+
+impl SubstitutionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the handler of `$(command ...)`, replacing the
+    /// previous one of the same command. The handler receives the
+    /// evaluated arguments, whose count is checked against `num_args`
+    /// beforehand, and returns the value or the reason of failure.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the command is built into ROS launch, e.g. `env`,
+    /// since its handler would never be called.
+    pub fn register<F>(
+        &mut self,
+        command: impl Into<String>,
+        num_args: RangeInclusive<usize>,
+        handler: F,
+    ) -> &mut Self
+    where
+        F: Fn(&[String]) -> Result<String, String> + 'static,
+    {
+        let command = command.into();
+        assert!(
+            !BUILTIN_COMMANDS.contains(&command.as_str()),
+            "the substitution \"{command}\" is built in and cannot be overridden"
+        );
+
+        self.entries.insert(
+            command,
+            Entry {
+                num_args,
+                handler: Box::new(handler),
+            },
+        );
+        self
+    }
+
+    pub fn contains(&self, command: &str) -> bool {
+        self.entries.contains_key(command)
+    }
+
+    /// Returns the registered command names in arbitrary order.
+    pub fn commands(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Checks the number of arguments given to the command. It returns
+    /// `None` if the command is not registered.
+    pub fn check_args(&self, command: &str, count: usize) -> Option<Result<(), SubstError>> {
+        let entry = self.entries.get(command)?;
+        let (min, max) = (*entry.num_args.start(), *entry.num_args.end());
+
+        if entry.num_args.contains(&count) {
+            return Some(Ok(()));
+        }
+
+        let noun = |count| if count == 1 { "argument" } else { "arguments" };
+        let expected = match (min, max) {
+            (min, max) if min == max => format!("{min} {}", noun(min)),
+            (min, usize::MAX) => format!("at least {min} {}", noun(min)),
+            (min, max) => format!("{min} to {max} {}", noun(max)),
+        };
+        Some(Err(SubstError::InvalidArgumentCount {
+            command: command.to_string(),
+            expected,
+            count,
+        }))
+    }
+
+    /// Runs the handler of the command. It returns `None` if the
+    /// command is not registered.
+    pub fn call(&self, command: &str, args: &[String]) -> Option<Result<String, SubstError>> {
+        let entry = self.entries.get(command)?;
+
+        let result = self.check_args(command, args.len())?.and_then(|()| {
+            (entry.handler)(args).map_err(|reason| SubstError::Custom {
+                command: command.to_string(),
+                reason,
+            })
+        });
+        Some(result)
+    }
+}
+
+impl fmt::Debug for SubstitutionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut commands: Vec<_> = self.commands().collect();
+        commands.sort_unstable();
+        f.debug_struct("SubstitutionRegistry")
+            .field("commands", &commands)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval_blocks, parse::parse, ProcessContext};
+
+    fn context() -> ProcessContext {
+        let mut ctx = ProcessContext::new();
+        ctx.registry
+            .register("serial", 0..=0, |_| Ok("SN-1".to_string()))
+            .register("join", 1..=usize::MAX, |args| Ok(args.join("-")))
+            .register("pick", 1..=2, |args| match args {
+                [first, ..] if first == "bad" => Err("no such item".to_string()),
+                [first, ..] => Ok(first.clone()),
+                [] => unreachable!(),
+            });
+        ctx
+    }
+
+    fn eval(text: &str) -> Result<String, SubstError> {
+        eval_blocks(&parse(text).unwrap(), &context())
+    }
+
+    #[test]
+    fn call_handlers() {
+        assert_eq!(
+            eval("$(serial)/$(join a $(serial) b)").unwrap(),
+            "SN-1/a-SN-1-b"
+        );
+        assert_eq!(eval("$(pick x y)").unwrap(), "x");
+
+        assert!(matches!(
+            eval("$(pick bad)"),
+            Err(SubstError::Custom { command, reason })
+                if command == "pick" && reason == "no such item"
+        ));
+        assert!(matches!(
+            eval("$(robot-model)"),
+            Err(SubstError::Unsupported { name }) if name == "robot-model"
+        ));
+    }
+
+    #[test]
+    fn argument_count() {
+        let message = |text| eval(text).unwrap_err().to_string();
+
+        assert_eq!(
+            message("$(serial a)"),
+            r#"the substitution "serial" expects 0 arguments, but get 1"#
+        );
+        assert_eq!(
+            message("$(join)"),
+            r#"the substitution "join" expects at least 1 argument, but get 0"#
+        );
+        assert_eq!(
+            message("$(pick a b c)"),
+            r#"the substitution "pick" expects 1 to 2 arguments, but get 3"#
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "built in")]
+    fn reject_builtin_commands() {
+        SubstitutionRegistry::new().register("env", 1..=1, |_| Ok(String::new()));
+    }
+}
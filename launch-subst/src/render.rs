@@ -0,0 +1,179 @@
+//! Rendering of parsed substitutions back to source text.
+//!
+//! The output is canonical: arguments are left unquoted when possible
+//! and quoted otherwise, and only the characters that would be
+//! misread are escaped. Parsing the rendered text gives back the same
+//! blocks.
+
+use crate::types::{SubstBlock, Substitution};
+use std::fmt::{self, Display, Write};
+
+/// Renders the blocks as substitution source text.
+pub fn to_source(blocks: &[SubstBlock]) -> String {
+    let mut buf = String::new();
+    for block in blocks {
+        write!(buf, "{block}").unwrap();
+    }
+    buf
+}
+
+impl Display for SubstBlock<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubstBlock::Text { text, .. } => write_text(f, text),
+            SubstBlock::Substitution { subst, .. } => write!(f, "{subst}"),
+        }
+    }
+}
+
+impl Display for Substitution<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$({}", self.command())?;
+
+        for arg in self.args() {
+            write!(f, " ")?;
+            write_arg(f, arg)?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+/// Writes literal text outside substitutions. A dollar sign is escaped
+/// if it starts `$(` or follows a backslash, since the parser reads
+/// `\$` as `$`.
+fn write_text(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    let mut prev = None;
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch == '$' && (chars.peek() == Some(&'(') || prev == Some('\\')) {
+            f.write_char('\\')?;
+        }
+        f.write_char(ch)?;
+        prev = Some(ch);
+    }
+
+    Ok(())
+}
+
+fn write_arg(f: &mut fmt::Formatter<'_>, arg: &[SubstBlock]) -> fmt::Result {
+    if can_be_unquoted(arg) {
+        for block in arg {
+            match block {
+                SubstBlock::Text { text, .. } => f.write_str(text)?,
+                SubstBlock::Substitution { subst, .. } => write!(f, "{subst}")?,
+            }
+        }
+        return Ok(());
+    }
+
+    // Prefer single quotes unless only double quotes avoid escaping.
+    let has_quote = |quote: char| {
+        arg.iter()
+            .any(|block| matches!(block, SubstBlock::Text { text, .. } if text.contains(quote)))
+    };
+    let quote = if has_quote('\'') && !has_quote('"') {
+        '"'
+    } else {
+        '\''
+    };
+
+    f.write_char(quote)?;
+
+    for block in arg {
+        match block {
+            SubstBlock::Text { text, .. } => {
+                let mut chars = text.chars().peekable();
+
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        '\\' => f.write_char('\\')?,
+                        _ if ch == quote => f.write_char('\\')?,
+                        '$' if chars.peek() == Some(&'(') => f.write_char('\\')?,
+                        _ => {}
+                    }
+                    f.write_char(ch)?;
+                }
+            }
+            SubstBlock::Substitution { subst, .. } => write!(f, "{subst}")?,
+        }
+    }
+
+    f.write_char(quote)
+}
+
+/// Checks if the argument text contains no characters that end an
+/// unquoted argument and no `$(` that would start a substitution.
+fn can_be_unquoted(arg: &[SubstBlock]) -> bool {
+    !arg.is_empty()
+        && arg.iter().all(|block| match block {
+            SubstBlock::Text { text, .. } => {
+                !text.contains("$(")
+                    && !text.contains(|ch: char| {
+                        matches!(ch, ' ' | '\t' | '\r' | '\n' | '\'' | '"' | '(' | ')')
+                    })
+            }
+            SubstBlock::Substitution { .. } => true,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    fn assert_round_trip(input: &str) {
+        let blocks = parse(input).unwrap();
+        let source = to_source(&blocks);
+        let reparsed = parse(&source).unwrap_or_else(|err| {
+            panic!("unable to parse {source:?} rendered from {input:?}\n{err}")
+        });
+        assert_eq!(reparsed, blocks, "{input:?} is rendered as {source:?}");
+    }
+
+    #[test]
+    fn round_trip() {
+        for input in [
+            "",
+            "plain text",
+            r"\$(env A)",
+            r"a\\$(env A)",
+            r"cost: 5$ \$",
+            "$(env A)",
+            "$(env A)$(env B)",
+            "$(var name)/config.yaml",
+            "$(env 'a\\\\$(env b)')",
+            r#"$(env "x\\")"#,
+            "$(env 'a)b')",
+            "$(env '')",
+            "$(env 'a b')",
+            r#"$(env "it's")"#,
+            r#"$(env 'say "hi"')"#,
+            r#"$(env 'both \' and "')"#,
+            "$(env '\\$(not a subst)')",
+            "$(env prefix$(var x)suffix)",
+            "$(env '$(var x) y')",
+            "$(eval '1 + 2')",
+            "$(find-pkg-share demo)/launch",
+        ] {
+            assert_round_trip(input);
+        }
+    }
+
+    #[test]
+    fn render_blocks() {
+        let blocks = vec![
+            SubstBlock::text("$(x) \\$", 0..0),
+            SubstBlock::Substitution {
+                subst: Substitution::Env {
+                    variable: crate::SubstArg::new(vec![SubstBlock::text("a'b", 0..0)], 0..0),
+                },
+                span: 0..0,
+            },
+        ];
+        let source = to_source(&blocks);
+        assert_eq!(source, r#"\$(x) \\$$(env "a'b")"#);
+        assert_eq!(parse(&source).unwrap(), blocks);
+    }
+}
@@ -0,0 +1,261 @@
+//! The substitution syntax of ROS 1 roslaunch.
+//!
+//! Substitutions are not nested and their arguments are separated by
+//! spaces without quoting. `$(eval)` must span the whole text, and its
+//! expression is taken verbatim. Unlike ROS 2 expressions, it may call
+//! `arg()`, `env()`, `optenv()`, `find()`, `anon()` and `dirname()` as
+//! in roslaunch. The supported commands are `env`, `optenv`, `find`,
+//! `anon`, `arg`, `dirname` and `eval`. The text after `$(find pkg)`,
+//! e.g. `/launch/robot.launch`, is kept as a path suffix of the package
+//! directory.
+
+use crate::{
+    parse::{Dialect, Rule},
+    types::{Span, SubstArg, SubstBlock, Substitution},
+};
+use pest::{
+    error::{Error, ErrorVariant},
+    Span as PestSpan,
+};
+use std::borrow::Cow;
+
+pub(crate) fn parse(input: &str) -> Result<Vec<SubstBlock<'_>>, Error<Rule>> {
+    if let Some(expr) = input
+        .strip_prefix("$(eval ")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let start = "$(eval ".len();
+        let subst = Substitution::Eval {
+            expr: arg(expr, start..start + expr.len()),
+            dialect: Dialect::Ros1,
+        };
+        return Ok(vec![SubstBlock::Substitution {
+            subst,
+            span: 0..input.len(),
+        }]);
+    }
+
+    let mut blocks = vec![];
+    let mut pos = 0;
+
+    while let Some(offset) = input[pos..].find("$(") {
+        let start = pos + offset;
+        let Some(len) = input[start..].find(')') else {
+            // roslaunch leaves an unterminated substitution as is.
+            break;
+        };
+        let end = start + len + 1;
+        let body = &input[start + 2..end - 1];
+
+        if let Some(offset) = body.find(['$', '(']) {
+            let at = start + 2 + offset;
+            return Err(error(
+                input,
+                at,
+                at + 1,
+                "substitutions cannot be nested".to_string(),
+            ));
+        }
+
+        if start > pos {
+            blocks.push(SubstBlock::text(&input[pos..start], pos..start));
+        }
+        let subst =
+            parse_subst(body, start + 2).map_err(|message| error(input, start, end, message))?;
+        blocks.push(SubstBlock::Substitution {
+            subst,
+            span: start..end,
+        });
+        pos = end;
+    }
+
+    if pos < input.len() {
+        blocks.push(SubstBlock::text(&input[pos..], pos..input.len()));
+    }
+
+    Ok(blocks)
+}
+
+/// Parses the body of `$(...)` starting at `offset` in the input.
+fn parse_subst(body: &str, offset: usize) -> Result<Substitution<'_>, String> {
+    let words = words(body, offset);
+    let Some(((command, _), args)) = words.split_first() else {
+        return Err("expect a substitution command".to_string());
+    };
+
+    let subst = match *command {
+        "env" => {
+            let [(var, span)] = args else {
+                return Err("expect one argument: ENVIRONMENT_VARIABLE".to_string());
+            };
+            Substitution::Env {
+                variable: arg(*var, span.clone()),
+            }
+        }
+        "optenv" => {
+            let Some(((var, span), default)) = args.split_first() else {
+                return Err("expect arguments: ENVIRONMENT_VARIABLE [DEFAULT_VALUE]".to_string());
+            };
+
+            // The default value is the rest of the words joined by
+            // single spaces.
+            let default_value = match default {
+                [] => None,
+                [(word, span)] => Some(arg(*word, span.clone())),
+                [(_, first), .., (_, last)] => {
+                    let text = default.iter().map(|(word, _)| *word).collect::<Vec<_>>();
+                    Some(arg(text.join(" "), first.start..last.end))
+                }
+            };
+
+            Substitution::OptEnv {
+                variable: arg(*var, span.clone()),
+                default_value,
+            }
+        }
+        "find" => {
+            let [(pkg, span)] = args else {
+                return Err("expect one argument: PACKAGE_NAME".to_string());
+            };
+            Substitution::Find {
+                pkg: arg(*pkg, span.clone()),
+            }
+        }
+        "anon" => {
+            let [(name, span)] = args else {
+                return Err("expect one argument: NAME".to_string());
+            };
+            Substitution::Anon {
+                name: arg(*name, span.clone()),
+            }
+        }
+        "arg" => {
+            let [(name, span)] = args else {
+                return Err("expect one argument: ARG_NAME".to_string());
+            };
+            Substitution::Arg {
+                name: arg(*name, span.clone()),
+            }
+        }
+        "dirname" => {
+            if !args.is_empty() {
+                return Err("expect no arguments".to_string());
+            }
+            Substitution::DirName
+        }
+        "eval" => {
+            return Err("$(eval) must span the whole text".to_string());
+        }
+        _ => {
+            return Err(format!(
+                r#"unknown substitution command "{command}", expect one of env, optenv, find, anon, arg, dirname or eval"#
+            ));
+        }
+    };
+
+    Ok(subst)
+}
+
+/// Splits the text into words separated by spaces, along with their
+/// spans offset by `offset`.
+fn words(text: &str, offset: usize) -> Vec<(&str, Span)> {
+    let mut words = vec![];
+    let mut pos = 0;
+
+    for word in text.split(' ') {
+        if !word.is_empty() {
+            words.push((word, offset + pos..offset + pos + word.len()));
+        }
+        pos += word.len() + 1;
+    }
+
+    words
+}
+
+fn arg<'a>(text: impl Into<Cow<'a, str>>, span: Span) -> SubstArg<'a> {
+    SubstArg::new(vec![SubstBlock::text(text, span.clone())], span)
+}
+
+fn error(input: &str, start: usize, end: usize, message: String) -> Error<Rule> {
+    Error::new_from_span(
+        ErrorVariant::CustomError { message },
+        PestSpan::new(input, start, end).unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deps::{dependencies, Dependency},
+        eval_blocks,
+        parse::{parse as parse_ros2, parse_with_dialect},
+        EvalError, ProcessContext, SubstError,
+    };
+
+    fn eval(text: &str, dialect: Dialect) -> Result<String, SubstError> {
+        let mut ctx = ProcessContext::new();
+        ctx.launch_configurations
+            .insert("robot".to_string(), "r1".to_string());
+        eval_blocks(&parse_with_dialect(text, dialect).unwrap(), &ctx)
+    }
+
+    #[test]
+    fn parse_substitutions() {
+        let blocks = parse("$(find nav)/launch/$(arg robot).launch").unwrap();
+        assert_eq!(blocks.len(), 4);
+        assert!(matches!(
+            &blocks[0],
+            SubstBlock::Substitution { subst: Substitution::Find { .. }, span } if *span == (0..11)
+        ));
+
+        let [SubstBlock::Substitution {
+            subst: Substitution::OptEnv { default_value, .. },
+            ..
+        }] = &parse("$(optenv NO_SUCH_VAR a  b c)").unwrap()[..]
+        else {
+            panic!("expect $(optenv)");
+        };
+        assert_eq!(default_value.as_ref().unwrap().span, 21..27);
+        assert_eq!(
+            eval("$(optenv NO_SUCH_VAR a  b c)", Dialect::Ros1).unwrap(),
+            "a b c"
+        );
+
+        assert!(parse("$(env $(arg robot))").is_err());
+        assert!(parse("a $(eval 1 + 2)").is_err());
+        assert!(parse("$(var robot)").is_err());
+    }
+
+    #[test]
+    fn roslaunch_functions() {
+        assert_eq!(
+            eval("$(eval arg('robot') + '_description')", Dialect::Ros1).unwrap(),
+            "r1_description"
+        );
+        assert_eq!(
+            eval("$(eval robot == 'r1')", Dialect::Ros1).unwrap(),
+            "True"
+        );
+
+        // ROS 2 expressions cannot call them.
+        assert!(matches!(
+            eval("$(eval \"arg('robot')\")", Dialect::Ros2),
+            Err(SubstError::Eval {
+                source: EvalError::UnknownFunction(_),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn roslaunch_function_dependencies() {
+        let ros1 = dependencies(&parse("$(eval env('HOME') + robot)").unwrap());
+        assert!(ros1.contains(&Dependency::Env("HOME".to_string())));
+        assert!(ros1.contains(&Dependency::LaunchConfiguration("robot".to_string())));
+
+        let ros2 = dependencies(&parse_ros2("$(eval \"env('HOME') + robot\")").unwrap());
+        assert!(!ros2.contains(&Dependency::Env("HOME".to_string())));
+        assert!(ros2.contains(&Dependency::LaunchConfiguration("robot".to_string())));
+    }
+}
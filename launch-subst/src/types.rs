@@ -1,64 +1,715 @@
-use anyhow::bail;
-use std::env::VarError;
+use crate::{
+    command::CommandOutput,
+    condition::{parse_bool, values_equal},
+    context::SubstitutionContext,
+    error::SubstError,
+    eval::{evaluate_with, EvalError, Value},
+    package::find_in_path,
+    parse::Dialect,
+};
+use std::{
+    borrow::Cow,
+    ops::{Deref, Range},
+    path::Path,
+};
 
+/// A byte range in the parsed text.
+pub type Span = Range<usize>;
+
+/// A run of literal text or a substitution.
+///
+/// Blocks borrow the parsed text where possible and carry their byte
+/// ranges in it. Blocks that are not parsed from text, e.g. the values
+/// of partially evaluated substitutions, carry the range of the text
+/// they replace. Spans are ignored by equality.
 #[derive(Debug, Clone)]
-pub enum SubstBlock {
-    Text(String),
-    Substitution(Substitution),
+pub enum SubstBlock<'a> {
+    Text { text: Cow<'a, str>, span: Span },
+    Substitution { subst: Substitution<'a>, span: Span },
 }
 
+/// An argument of a substitution.
+///
+/// The span covers the argument as written, including quotes. It
+/// dereferences to its blocks.
 #[derive(Debug, Clone)]
-pub enum Substitution {
+pub struct SubstArg<'a> {
+    pub blocks: Vec<SubstBlock<'a>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Substitution<'a> {
     Env {
-        variable: String,
+        variable: SubstArg<'a>,
     },
     OptEnv {
-        variable: String,
-        default_value: Option<String>,
+        variable: SubstArg<'a>,
+        default_value: Option<SubstArg<'a>>,
     },
     Find {
-        pkg: String,
+        pkg: SubstArg<'a>,
+    },
+    FindPkgShare {
+        pkg: SubstArg<'a>,
+    },
+    FindPkgPrefix {
+        pkg: SubstArg<'a>,
     },
     Anon {
-        name: String,
+        name: SubstArg<'a>,
     },
     Arg {
-        name: String,
+        name: SubstArg<'a>,
+    },
+    Var {
+        name: SubstArg<'a>,
+    },
+    LaunchConfiguration {
+        name: SubstArg<'a>,
+    },
+    FindExec {
+        name: SubstArg<'a>,
+    },
+    ExecInPkg {
+        exec: SubstArg<'a>,
+        pkg: SubstArg<'a>,
+    },
+    Command {
+        command: SubstArg<'a>,
+        on_stderr: Option<SubstArg<'a>>,
+    },
+    Param {
+        name: SubstArg<'a>,
     },
     Eval {
-        expr: String,
+        expr: SubstArg<'a>,
+        /// The dialect of the expression. ROS 1 expressions may call
+        /// the roslaunch functions, e.g. `find('nav')`.
+        dialect: Dialect,
+    },
+    Not {
+        value: SubstArg<'a>,
+    },
+    And {
+        lhs: SubstArg<'a>,
+        rhs: SubstArg<'a>,
+    },
+    Or {
+        lhs: SubstArg<'a>,
+        rhs: SubstArg<'a>,
+    },
+    Equals {
+        lhs: SubstArg<'a>,
+        rhs: SubstArg<'a>,
+    },
+    NotEquals {
+        lhs: SubstArg<'a>,
+        rhs: SubstArg<'a>,
     },
     DirName,
+    FileName,
+    /// A substitution that is not known to the parser.
     Other {
-        args: Vec<String>,
+        command: Cow<'a, str>,
+        args: Vec<SubstArg<'a>>,
     },
 }
 
-impl Substitution {
-    pub fn eval(&self) -> anyhow::Result<String> {
+impl<'a> SubstBlock<'a> {
+    pub fn text(text: impl Into<Cow<'a, str>>, span: Span) -> Self {
+        SubstBlock::Text {
+            text: text.into(),
+            span,
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        match self {
+            SubstBlock::Text { span, .. } | SubstBlock::Substitution { span, .. } => span,
+        }
+    }
+
+    /// Converts the block to one that owns its text.
+    pub fn into_owned(self) -> SubstBlock<'static> {
+        match self {
+            SubstBlock::Text { text, span } => SubstBlock::Text {
+                text: Cow::Owned(text.into_owned()),
+                span,
+            },
+            SubstBlock::Substitution { subst, span } => SubstBlock::Substitution {
+                subst: subst.into_owned(),
+                span,
+            },
+        }
+    }
+
+    pub fn eval<C>(&self, ctx: &C) -> Result<String, SubstError>
+    where
+        C: SubstitutionContext + ?Sized,
+    {
+        match self {
+            SubstBlock::Text { text, .. } => Ok(text.to_string()),
+            SubstBlock::Substitution { subst, .. } => subst.eval(ctx),
+        }
+    }
+}
+
+impl PartialEq for SubstBlock<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SubstBlock::Text { text: lhs, .. }, SubstBlock::Text { text: rhs, .. }) => lhs == rhs,
+            (
+                SubstBlock::Substitution { subst: lhs, .. },
+                SubstBlock::Substitution { subst: rhs, .. },
+            ) => lhs == rhs,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> SubstArg<'a> {
+    pub fn new(blocks: Vec<SubstBlock<'a>>, span: Span) -> Self {
+        Self { blocks, span }
+    }
+
+    /// Converts the argument to one that owns its text.
+    pub fn into_owned(self) -> SubstArg<'static> {
+        SubstArg {
+            blocks: self
+                .blocks
+                .into_iter()
+                .map(SubstBlock::into_owned)
+                .collect(),
+            span: self.span,
+        }
+    }
+}
+
+impl<'a> Deref for SubstArg<'a> {
+    type Target = [SubstBlock<'a>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.blocks
+    }
+}
+
+impl PartialEq for SubstArg<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.blocks == other.blocks
+    }
+}
+
+/// Evaluates the blocks and concatenates the results.
+pub fn eval_blocks<C>(blocks: &[SubstBlock], ctx: &C) -> Result<String, SubstError>
+where
+    C: SubstitutionContext + ?Sized,
+{
+    blocks.iter().map(|block| block.eval(ctx)).collect()
+}
+
+fn eval_condition<C>(blocks: &[SubstBlock], ctx: &C) -> Result<bool, SubstError>
+where
+    C: SubstitutionContext + ?Sized,
+{
+    let value = eval_blocks(blocks, ctx)?;
+    parse_bool(&value).ok_or(SubstError::InvalidCondition { value })
+}
+
+fn eval_expr<C>(expr: &[SubstBlock], dialect: Dialect, ctx: &C) -> Result<Value, SubstError>
+where
+    C: SubstitutionContext + ?Sized,
+{
+    let expr = eval_blocks(expr, ctx)?;
+    evaluate_with(
+        &expr,
+        |name| ctx.launch_configuration(name),
+        |name, args| match dialect {
+            Dialect::Ros1 => roslaunch_function(name, args, ctx),
+            Dialect::Ros2 => None,
+        },
+    )
+    .map_err(|source| SubstError::Eval { expr, source })
+}
+
+/// Calls the functions that roslaunch provides to `$(eval)`.
+fn roslaunch_function<C>(name: &str, args: &[Value], ctx: &C) -> Option<Result<Value, EvalError>>
+where
+    C: SubstitutionContext + ?Sized,
+{
+    let result = roslaunch_substitution(name, args)?.and_then(|subst| {
+        subst
+            .eval(ctx)
+            .map(Value::Str)
+            .map_err(|err| EvalError::Value(err.to_string()))
+    });
+    Some(result)
+}
+
+/// Returns the substitution that the roslaunch function in `$(eval)`
+/// behaves like, or `None` if it is not a roslaunch function.
+pub(crate) fn roslaunch_substitution(
+    name: &str,
+    args: &[Value],
+) -> Option<Result<Substitution<'static>, EvalError>> {
+    // The arguments are values rather than source text, so they have
+    // no spans.
+    let text = |value: &Value| SubstArg::new(vec![SubstBlock::text(value.to_string(), 0..0)], 0..0);
+
+    let subst = match (name, args) {
+        ("arg", [name]) => Substitution::Arg { name: text(name) },
+        ("env", [variable]) => Substitution::Env {
+            variable: text(variable),
+        },
+        ("optenv", [variable]) => Substitution::OptEnv {
+            variable: text(variable),
+            default_value: None,
+        },
+        ("optenv", [variable, default]) => Substitution::OptEnv {
+            variable: text(variable),
+            default_value: Some(text(default)),
+        },
+        ("find", [pkg]) => Substitution::Find { pkg: text(pkg) },
+        ("anon", [name]) => Substitution::Anon { name: text(name) },
+        ("dirname", []) => Substitution::DirName,
+        ("arg" | "env" | "optenv" | "find" | "anon" | "dirname", _) => {
+            let expected = match name {
+                "optenv" => "1 or 2 arguments",
+                "dirname" => "no arguments",
+                _ => "exactly one argument",
+            };
+            return Some(Err(EvalError::Type(format!(
+                "{name}() takes {expected} but {} were given",
+                args.len()
+            ))));
+        }
+        _ => return None,
+    };
+
+    Some(Ok(subst))
+}
+
+impl<'a> Substitution<'a> {
+    /// Returns the command name as written in `$(...)`.
+    pub fn command(&self) -> &str {
+        match self {
+            Substitution::Env { .. } => "env",
+            Substitution::OptEnv { .. } => "optenv",
+            Substitution::Find { .. } => "find",
+            Substitution::FindPkgShare { .. } => "find-pkg-share",
+            Substitution::FindPkgPrefix { .. } => "find-pkg-prefix",
+            Substitution::Anon { .. } => "anon",
+            Substitution::Arg { .. } => "arg",
+            Substitution::Var { .. } => "var",
+            Substitution::LaunchConfiguration { .. } => "launch_configuration",
+            Substitution::FindExec { .. } => "find-exec",
+            Substitution::ExecInPkg { .. } => "exec-in-pkg",
+            Substitution::Command { .. } => "command",
+            Substitution::Param { .. } => "param",
+            Substitution::Eval { .. } => "eval",
+            Substitution::Not { .. } => "not",
+            Substitution::And { .. } => "and",
+            Substitution::Or { .. } => "or",
+            Substitution::Equals { .. } => "equals",
+            Substitution::NotEquals { .. } => "not-equals",
+            Substitution::DirName => "dirname",
+            Substitution::FileName => "filename",
+            Substitution::Other { command, .. } => command,
+        }
+    }
+
+    /// Returns the arguments in the order they are written.
+    pub fn args(&self) -> Vec<&SubstArg<'a>> {
+        match self {
+            Substitution::Env { variable } => vec![variable],
+            Substitution::OptEnv {
+                variable,
+                default_value,
+            } => [Some(variable), default_value.as_ref()]
+                .into_iter()
+                .flatten()
+                .collect(),
+            Substitution::Find { pkg }
+            | Substitution::FindPkgShare { pkg }
+            | Substitution::FindPkgPrefix { pkg } => vec![pkg],
+            Substitution::Anon { name }
+            | Substitution::Arg { name }
+            | Substitution::Var { name }
+            | Substitution::LaunchConfiguration { name }
+            | Substitution::FindExec { name }
+            | Substitution::Param { name } => vec![name],
+            Substitution::ExecInPkg { exec, pkg } => vec![exec, pkg],
+            Substitution::Command { command, on_stderr } => [Some(command), on_stderr.as_ref()]
+                .into_iter()
+                .flatten()
+                .collect(),
+            Substitution::Eval { expr, .. } => vec![expr],
+            Substitution::Not { value } => vec![value],
+            Substitution::And { lhs, rhs }
+            | Substitution::Or { lhs, rhs }
+            | Substitution::Equals { lhs, rhs }
+            | Substitution::NotEquals { lhs, rhs } => vec![lhs, rhs],
+            Substitution::DirName | Substitution::FileName => vec![],
+            Substitution::Other { args, .. } => args.iter().collect(),
+        }
+    }
+
+    /// Returns a copy of the substitution with each argument replaced
+    /// by the result of `f`. The command name of
+    /// [`Substitution::Other`] stays borrowed.
+    pub fn map_args<'b, F>(&self, mut f: F) -> Substitution<'b>
+    where
+        'a: 'b,
+        F: FnMut(&SubstArg<'a>) -> SubstArg<'b>,
+    {
+        match self {
+            Substitution::Env { variable } => Substitution::Env {
+                variable: f(variable),
+            },
+            Substitution::OptEnv {
+                variable,
+                default_value,
+            } => Substitution::OptEnv {
+                variable: f(variable),
+                default_value: default_value.as_ref().map(f),
+            },
+            Substitution::Find { pkg } => Substitution::Find { pkg: f(pkg) },
+            Substitution::FindPkgShare { pkg } => Substitution::FindPkgShare { pkg: f(pkg) },
+            Substitution::FindPkgPrefix { pkg } => Substitution::FindPkgPrefix { pkg: f(pkg) },
+            Substitution::Anon { name } => Substitution::Anon { name: f(name) },
+            Substitution::Arg { name } => Substitution::Arg { name: f(name) },
+            Substitution::Var { name } => Substitution::Var { name: f(name) },
+            Substitution::LaunchConfiguration { name } => {
+                Substitution::LaunchConfiguration { name: f(name) }
+            }
+            Substitution::FindExec { name } => Substitution::FindExec { name: f(name) },
+            Substitution::ExecInPkg { exec, pkg } => Substitution::ExecInPkg {
+                exec: f(exec),
+                pkg: f(pkg),
+            },
+            Substitution::Command { command, on_stderr } => Substitution::Command {
+                command: f(command),
+                on_stderr: on_stderr.as_ref().map(f),
+            },
+            Substitution::Param { name } => Substitution::Param { name: f(name) },
+            Substitution::Eval { expr, dialect } => Substitution::Eval {
+                expr: f(expr),
+                dialect: *dialect,
+            },
+            Substitution::Not { value } => Substitution::Not { value: f(value) },
+            Substitution::And { lhs, rhs } => Substitution::And {
+                lhs: f(lhs),
+                rhs: f(rhs),
+            },
+            Substitution::Or { lhs, rhs } => Substitution::Or {
+                lhs: f(lhs),
+                rhs: f(rhs),
+            },
+            Substitution::Equals { lhs, rhs } => Substitution::Equals {
+                lhs: f(lhs),
+                rhs: f(rhs),
+            },
+            Substitution::NotEquals { lhs, rhs } => Substitution::NotEquals {
+                lhs: f(lhs),
+                rhs: f(rhs),
+            },
+            Substitution::DirName => Substitution::DirName,
+            Substitution::FileName => Substitution::FileName,
+            Substitution::Other { command, args } => Substitution::Other {
+                command: command.clone(),
+                args: args.iter().map(f).collect(),
+            },
+        }
+    }
+
+    /// Converts the substitution to one that owns its text.
+    pub fn into_owned(self) -> Substitution<'static> {
+        let f = SubstArg::into_owned;
+
+        match self {
+            Substitution::Env { variable } => Substitution::Env {
+                variable: f(variable),
+            },
+            Substitution::OptEnv {
+                variable,
+                default_value,
+            } => Substitution::OptEnv {
+                variable: f(variable),
+                default_value: default_value.map(f),
+            },
+            Substitution::Find { pkg } => Substitution::Find { pkg: f(pkg) },
+            Substitution::FindPkgShare { pkg } => Substitution::FindPkgShare { pkg: f(pkg) },
+            Substitution::FindPkgPrefix { pkg } => Substitution::FindPkgPrefix { pkg: f(pkg) },
+            Substitution::Anon { name } => Substitution::Anon { name: f(name) },
+            Substitution::Arg { name } => Substitution::Arg { name: f(name) },
+            Substitution::Var { name } => Substitution::Var { name: f(name) },
+            Substitution::LaunchConfiguration { name } => {
+                Substitution::LaunchConfiguration { name: f(name) }
+            }
+            Substitution::FindExec { name } => Substitution::FindExec { name: f(name) },
+            Substitution::ExecInPkg { exec, pkg } => Substitution::ExecInPkg {
+                exec: f(exec),
+                pkg: f(pkg),
+            },
+            Substitution::Command { command, on_stderr } => Substitution::Command {
+                command: f(command),
+                on_stderr: on_stderr.map(f),
+            },
+            Substitution::Param { name } => Substitution::Param { name: f(name) },
+            Substitution::Eval { expr, dialect } => Substitution::Eval {
+                expr: f(expr),
+                dialect,
+            },
+            Substitution::Not { value } => Substitution::Not { value: f(value) },
+            Substitution::And { lhs, rhs } => Substitution::And {
+                lhs: f(lhs),
+                rhs: f(rhs),
+            },
+            Substitution::Or { lhs, rhs } => Substitution::Or {
+                lhs: f(lhs),
+                rhs: f(rhs),
+            },
+            Substitution::Equals { lhs, rhs } => Substitution::Equals {
+                lhs: f(lhs),
+                rhs: f(rhs),
+            },
+            Substitution::NotEquals { lhs, rhs } => Substitution::NotEquals {
+                lhs: f(lhs),
+                rhs: f(rhs),
+            },
+            Substitution::DirName => Substitution::DirName,
+            Substitution::FileName => Substitution::FileName,
+            Substitution::Other { command, args } => Substitution::Other {
+                command: Cow::Owned(command.into_owned()),
+                args: args.into_iter().map(f).collect(),
+            },
+        }
+    }
+
+    pub fn eval<C>(&self, ctx: &C) -> Result<String, SubstError>
+    where
+        C: SubstitutionContext + ?Sized,
+    {
         let text = match self {
-            Substitution::Env { variable } => std::env::var(variable)?,
+            Substitution::Env { variable } => {
+                let name = eval_blocks(variable, ctx)?;
+                match ctx.env(&name) {
+                    Some(value) => value,
+                    None => return Err(SubstError::UndefinedEnv { name }),
+                }
+            }
             Substitution::OptEnv {
                 variable,
                 default_value,
-            } => match std::env::var(variable) {
-                Ok(value) => value,
-                Err(VarError::NotPresent) => match default_value {
-                    Some(value) => value.to_string(),
-                    None => bail!("the value of '{variable}' is not set"),
-                },
-                Err(VarError::NotUnicode(_value)) => {
-                    bail!("the value of '{variable}' is not Unicode")
+            } => {
+                let name = eval_blocks(variable, ctx)?;
+                match (ctx.env(&name), default_value) {
+                    (Some(value), _) => value,
+                    (None, Some(default)) => eval_blocks(default, ctx)?,
+                    (None, None) => String::new(),
                 }
-            },
-            Substitution::Find { pkg } => todo!(),
-            Substitution::Anon { name } => todo!(),
-            Substitution::Arg { name } => todo!(),
-            Substitution::Eval { expr } => todo!(),
-            Substitution::DirName => todo!(),
-            Substitution::Other { args } => todo!(),
+            }
+            Substitution::Find { pkg } | Substitution::FindPkgShare { pkg } => {
+                let pkg = eval_blocks(pkg, ctx)?;
+                match ctx.locator().find_share(&pkg) {
+                    Some(path) => path.display().to_string(),
+                    None => return Err(SubstError::PackageNotFound { pkg }),
+                }
+            }
+            Substitution::FindPkgPrefix { pkg } => {
+                let pkg = eval_blocks(pkg, ctx)?;
+                match ctx.locator().find_prefix(&pkg) {
+                    Some(path) => path.display().to_string(),
+                    None => return Err(SubstError::PackageNotFound { pkg }),
+                }
+            }
+            Substitution::FindExec { name } => {
+                let name = eval_blocks(name, ctx)?;
+                let path_var = ctx.env("PATH").unwrap_or_default();
+                match find_in_path(&name, path_var.as_ref()) {
+                    Some(path) => path.display().to_string(),
+                    None => return Err(SubstError::ExecutableNotFound { name }),
+                }
+            }
+            Substitution::ExecInPkg { exec, pkg } => {
+                let exec = eval_blocks(exec, ctx)?;
+                let pkg = eval_blocks(pkg, ctx)?;
+                let locator = ctx.locator();
+
+                if locator.find_prefix(&pkg).is_none() {
+                    return Err(SubstError::PackageNotFound { pkg });
+                }
+                match locator.find_executable(&pkg, &exec) {
+                    Some(path) => path.display().to_string(),
+                    None => {
+                        return Err(SubstError::ExecutableNotFound {
+                            name: format!("{pkg}/{exec}"),
+                        })
+                    }
+                }
+            }
+            Substitution::Anon { name } => ctx.anon_name(&eval_blocks(name, ctx)?),
+            Substitution::Arg { name }
+            | Substitution::Var { name }
+            | Substitution::LaunchConfiguration { name } => {
+                let name = eval_blocks(name, ctx)?;
+                match ctx.launch_configuration(&name) {
+                    Some(value) => value,
+                    None => return Err(SubstError::UndefinedLaunchConfiguration { name }),
+                }
+            }
+            Substitution::Command { command, on_stderr } => {
+                let command = eval_blocks(command, ctx)?;
+                let on_stderr = match on_stderr {
+                    Some(mode) => eval_blocks(mode, ctx)?,
+                    None => "fail".to_string(),
+                };
+                run_command(ctx, command, &on_stderr)?
+            }
+            Substitution::Param { .. } => {
+                return Err(SubstError::Unsupported {
+                    name: "param".to_string(),
+                })
+            }
+            Substitution::Eval { expr, dialect } => eval_expr(expr, *dialect, ctx)?.to_string(),
+            Substitution::Not { value } => (!eval_condition(value, ctx)?).to_string(),
+            Substitution::And { lhs, rhs } => {
+                (eval_condition(lhs, ctx)? && eval_condition(rhs, ctx)?).to_string()
+            }
+            Substitution::Or { lhs, rhs } => {
+                (eval_condition(lhs, ctx)? || eval_condition(rhs, ctx)?).to_string()
+            }
+            Substitution::Equals { lhs, rhs } => {
+                values_equal(&eval_blocks(lhs, ctx)?, &eval_blocks(rhs, ctx)?).to_string()
+            }
+            Substitution::NotEquals { lhs, rhs } => {
+                (!values_equal(&eval_blocks(lhs, ctx)?, &eval_blocks(rhs, ctx)?)).to_string()
+            }
+            Substitution::DirName => {
+                let file = ctx.current_file().ok_or(SubstError::UnknownFile)?;
+                let dir = file.parent().unwrap_or(Path::new("/"));
+                dir.display().to_string()
+            }
+            Substitution::FileName => {
+                let file = ctx.current_file().ok_or(SubstError::UnknownFile)?;
+                file.display().to_string()
+            }
+            Substitution::Other { command, args } => {
+                let registry = ctx.registry();
+                if !registry.contains(command) {
+                    return Err(SubstError::Unsupported {
+                        name: command.to_string(),
+                    });
+                }
+
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| eval_blocks(arg, ctx))
+                    .collect::<Result<_, _>>()?;
+                registry.call(command, &args).unwrap()?
+            }
         };
 
         Ok(text)
     }
+
+    /// Evaluates the substitution to a typed value. Only `$(eval)`
+    /// gives values other than strings.
+    pub fn eval_value<C>(&self, ctx: &C) -> Result<Value, SubstError>
+    where
+        C: SubstitutionContext + ?Sized,
+    {
+        match self {
+            Substitution::Eval { expr, dialect } => eval_expr(expr, *dialect, ctx),
+            _ => Ok(Value::Str(self.eval(ctx)?)),
+        }
+    }
+}
+
+/// Runs the command for `$(command)` and handles its error output
+/// according to the `on_stderr` mode as ROS does. Warnings are not
+/// reported, so `warn` behaves as `ignore`.
+fn run_command<C>(ctx: &C, command: String, on_stderr: &str) -> Result<String, SubstError>
+where
+    C: SubstitutionContext + ?Sized,
+{
+    if !["fail", "ignore", "warn", "capture"].contains(&on_stderr) {
+        return Err(SubstError::Command {
+            command,
+            reason: format!(
+                "expect ON_STDERR to be 'fail', 'ignore', 'warn' or 'capture', but get '{on_stderr}'"
+            ),
+        });
+    }
+
+    let CommandOutput {
+        status,
+        mut stdout,
+        stderr,
+    } = ctx.run_command(&command)?;
+
+    if status != Some(0) {
+        let reason = match status {
+            Some(code) => format!("exit with status {code}"),
+            None => "terminated by a signal".to_string(),
+        };
+        return Err(SubstError::Command { command, reason });
+    }
+
+    match on_stderr {
+        "fail" if !stderr.is_empty() => {
+            return Err(SubstError::Command {
+                command,
+                reason: format!("unexpected error output: {}", stderr.trim_end()),
+            })
+        }
+        "capture" => stdout.push_str(&stderr),
+        _ => {}
+    }
+
+    Ok(stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn into_owned() {
+        let text = "a $(env 'x y') $(robot-serial $(var id))".to_string();
+        let blocks = parse(&text).unwrap();
+        let owned: Vec<SubstBlock<'static>> =
+            blocks.iter().cloned().map(SubstBlock::into_owned).collect();
+        drop(text);
+
+        assert_eq!(owned.len(), 4);
+        assert_eq!(owned[1].span(), &(2..14));
+        let SubstBlock::Substitution {
+            subst: Substitution::Other { command, args },
+            ..
+        } = &owned[3]
+        else {
+            panic!("expect a user-defined substitution");
+        };
+        assert_eq!(command, "robot-serial");
+        assert_eq!(args[0].span, 30..39);
+    }
+
+    #[test]
+    fn map_args_borrows_command() {
+        let blocks = parse("$(robot-serial a)").unwrap();
+        let [SubstBlock::Substitution { subst, .. }] = &blocks[..] else {
+            panic!("expect one substitution");
+        };
+
+        let mapped = subst.map_args(|arg| SubstArg::new(vec![], arg.span.clone()));
+        assert!(matches!(
+            mapped,
+            Substitution::Other {
+                command: Cow::Borrowed("robot-serial"),
+                ..
+            }
+        ));
+    }
 }
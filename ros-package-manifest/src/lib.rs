@@ -1,7 +1,9 @@
-use std::borrow::Cow;
-use strong_xml::{XmlRead, XmlWrite};
+use std::{borrow::Cow, io::Write};
+use strong_xml::{XmlRead, XmlResult, XmlWrite, XmlWriter};
 
-#[derive(Debug, Clone, XmlRead, XmlWrite)]
+// XmlWrite is implemented by hand for elements with required children,
+// since the derive discards the results of writing them.
+#[derive(Debug, Clone, XmlRead)]
 #[xml(tag = "package")]
 pub struct Package<'a> {
     #[xml(attr = "format")]
@@ -79,7 +81,7 @@ pub struct ExecDepend<'a>(#[xml(text)] pub Cow<'a, str>);
 #[xml(tag = "test_depend")]
 pub struct TestDepend<'a>(#[xml(text)] pub Cow<'a, str>);
 
-#[derive(Debug, Clone, XmlRead, XmlWrite)]
+#[derive(Debug, Clone, XmlRead)]
 #[xml(tag = "export")]
 pub struct Export<'a> {
     #[xml(child = "build_type")]
@@ -89,3 +91,47 @@ pub struct Export<'a> {
 #[derive(Debug, Clone, XmlRead, XmlWrite)]
 #[xml(tag = "build_type")]
 pub struct BuildType<'a>(#[xml(text)] pub Cow<'a, str>);
+
+impl XmlWrite for Package<'_> {
+    fn to_writer<W: Write>(&self, writer: &mut XmlWriter<W>) -> XmlResult<()> {
+        writer.write_element_start("package")?;
+        writer.write_attribute("format", &self.format.to_string())?;
+        writer.write_element_end_open()?;
+
+        self.name.to_writer(writer)?;
+        self.version.to_writer(writer)?;
+        self.description.to_writer(writer)?;
+        self.license.to_writer(writer)?;
+        for ele in &self.maintainer {
+            ele.to_writer(writer)?;
+        }
+        for ele in &self.depend {
+            ele.to_writer(writer)?;
+        }
+        for ele in &self.buildtool_depend {
+            ele.to_writer(writer)?;
+        }
+        for ele in &self.exec_depend {
+            ele.to_writer(writer)?;
+        }
+        for ele in &self.test_depend {
+            ele.to_writer(writer)?;
+        }
+        self.export.to_writer(writer)?;
+
+        writer.write_element_end_close("package")?;
+
+        Ok(())
+    }
+}
+
+impl XmlWrite for Export<'_> {
+    fn to_writer<W: Write>(&self, writer: &mut XmlWriter<W>) -> XmlResult<()> {
+        writer.write_element_start("export")?;
+        writer.write_element_end_open()?;
+        self.build_type.to_writer(writer)?;
+        writer.write_element_end_close("export")?;
+
+        Ok(())
+    }
+}
@@ -9,6 +9,16 @@ use std::{
 use strong_xml::XmlRead;
 
 pub fn resolve<P>(dir: P) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    scan_packages(dir)?;
+    Ok(())
+}
+
+/// Finds all packages under the directory and returns a map from
+/// package names to the directories containing their manifest files.
+pub fn scan_packages<P>(dir: P) -> Result<HashMap<String, PathBuf>>
 where
     P: AsRef<Path>,
 {
@@ -30,24 +40,24 @@ where
     let manifests = manifests?;
 
     // Check duplicated package names
-    {
-        let mut names: HashMap<&str, &Path> = HashMap::with_capacity(manifests.len());
-
-        for (path, manifest) in izip!(&manifest_paths, &manifests) {
-            let name: &str = &manifest.name.0;
-            match names.entry(name) {
-                Entry::Occupied(entry) => {
-                    let offending_path = entry.get();
-                    bail!("the manifest files {offending_path:?} and {path:?} have the same package name '{name}'");
-                }
-                Entry::Vacant(entry) => {
-                    entry.insert(path);
-                }
+    let mut packages: HashMap<String, PathBuf> = HashMap::with_capacity(manifests.len());
+
+    for (path, manifest) in izip!(&manifest_paths, &manifests) {
+        let name: &str = &manifest.name.0;
+        let pkg_dir = path.parent().unwrap();
+
+        match packages.entry(name.to_string()) {
+            Entry::Occupied(entry) => {
+                let offending_dir = entry.get();
+                bail!("the packages at {offending_dir:?} and {pkg_dir:?} have the same package name '{name}'");
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(pkg_dir.to_path_buf());
             }
         }
     }
 
-    Ok(())
+    Ok(packages)
 }
 
 fn find_manifest_files(dir: impl AsRef<Path>) -> Vec<PathBuf> {